@@ -4,12 +4,19 @@ use scheduler::{
     task::{
         action::{ActionType, TaskAction},
         action_executor::ActionExecutor,
+        dead_letter::DeadLetter,
         default::Task,
+        retry_policy::RetryPolicy,
     },
 };
-use teloxide::{Bot, types::ChatId};
-
-use crate::engine::utils::send_chat_message_markdown;
+use std::time::Duration;
+use teloxide::{
+    ApiError, Bot, RequestError,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, ParseMode},
+    utils::markdown,
+};
 
 pub struct BotExecutor {
     bot: Bot,
@@ -21,6 +28,48 @@ impl BotExecutor {
     }
 }
 
+fn to_scheduler_error(error: RequestError) -> SchedulerError {
+    match error {
+        RequestError::RetryAfter(retry_after) => SchedulerError::RetryAfter(retry_after.duration()),
+        // Sending to this chat won't succeed no matter how many times we retry.
+        RequestError::Api(
+            ApiError::BotBlocked
+            | ApiError::BotKicked
+            | ApiError::BotKickedFromSupergroup
+            | ApiError::BotKickedFromChannel
+            | ApiError::ChatNotFound
+            | ApiError::GroupDeactivated
+            | ApiError::UserDeactivated
+            | ApiError::CantInitiateConversation
+            | ApiError::CantTalkWithBots
+            | ApiError::CantParseEntities(_)
+            | ApiError::MessageIsTooLong,
+        )
+        | RequestError::MigrateToChatId(_) => {
+            SchedulerError::PermanentExecutionError(error.to_string())
+        }
+        _ => SchedulerError::TaskExecutionError(error.to_string()),
+    }
+}
+
+/// The message notifying an administrator about a dead-lettered task. It's sent as
+/// MarkdownV2, like every other message of the executor.
+pub fn dead_letter_message(dead_letter: &DeadLetter) -> String {
+    let task = &dead_letter.task;
+    let task_id = markdown::escape(&task.id.to_string());
+    let task_name = match &task.metadata.title {
+        Some(title) => format!("'{}' \\({}\\)", markdown::escape(title), task_id),
+        None => task_id,
+    };
+
+    format!(
+        "⚠️ Zadatak {} nije izvršen nakon {} pokušaja: {}",
+        task_name,
+        dead_letter.attempts,
+        markdown::escape(&dead_letter.error)
+    )
+}
+
 #[async_trait]
 impl ActionExecutor for BotExecutor {
     fn supported_actions(&self) -> Vec<ActionType> {
@@ -29,9 +78,23 @@ impl ActionExecutor for BotExecutor {
 
     async fn execute(&self, _task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
         if let TaskAction::SendBotMessage { chat_id, message } = action {
-            send_chat_message_markdown(&self.bot, ChatId(*chat_id), message.clone()).await;
+            self.bot
+                .send_message(ChatId(*chat_id), message)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await
+                .map_err(to_scheduler_error)?;
         }
 
         Ok(())
     }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        // Spread out the retries of reminders that failed together, e.g. during a
+        // Telegram outage.
+        Some(RetryPolicy::exponential_with_jitter().with_max_delay(Duration::from_secs(600)))
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(60))
+    }
 }
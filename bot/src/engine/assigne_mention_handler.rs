@@ -1,5 +1,8 @@
 use chrono_tz::Europe::Sarajevo;
-use scheduler::task::{action::TaskAction, default::Task, task_scheduler::TaskScheduler};
+use scheduler::task::{
+    action::TaskAction, default::Task, local_time::resolve_local_datetime, metadata::TaskMetadata,
+    task_scheduler::TaskScheduler,
+};
 use teloxide::{
     Bot,
     types::{Message, MessageEntityKind},
@@ -8,6 +11,7 @@ use teloxide::{
 
 use crate::engine::{
     dialogue_handler::{TaskDialogue, TaskState},
+    interval_keyboard::{get_interval_label, parse_interval},
     utils::{
         CALENDAR_DEFAULT_DATE_FORMAT, ChatHandlerResult, TIME_DEFAULT_FORMAT, send_chat_message,
         send_chat_message_markdown,
@@ -22,13 +26,14 @@ pub async fn handle_assigne_mention_callback(
 ) -> ChatHandlerResult {
     let state = dialogue.get().await?.ok_or("Dialogue state not found")?;
 
-    let (task_name, date, time, end_date) = match state {
+    let (task_name, date, time, end_date, interval) = match state {
         TaskState::AwaitingAssigneeMention {
             task_name,
             date,
             time,
             end_date,
-        } => (task_name, date, time, end_date),
+            interval,
+        } => (task_name, date, time, end_date, interval),
         _ => return Err("Invalid dialogue state".into()),
     };
 
@@ -69,11 +74,7 @@ pub async fn handle_assigne_mention_callback(
                 &format!("{} {}", date, time),
                 &format!("{} {}", CALENDAR_DEFAULT_DATE_FORMAT, TIME_DEFAULT_FORMAT),
             )?;
-            let next_run = next_run
-                .and_local_timezone(Sarajevo)
-                .single()
-                .ok_or("Failed to convert to timezone-aware datetime")?
-                .with_timezone(&chrono::Utc);
+            let next_run = resolve_local_datetime(&Sarajevo, next_run);
 
             let action = TaskAction::SendBotMessage {
                 chat_id: msg.chat.id.0,
@@ -84,25 +85,41 @@ pub async fn handle_assigne_mention_callback(
                 ),
             };
 
+            let metadata = TaskMetadata {
+                title: Some(task_name.clone()),
+                created_by: msg.from.as_ref().map(|user| user.id.to_string()),
+                assignee: Some(mention.clone()),
+                chat_id: Some(msg.chat.id.0),
+                ..Default::default()
+            };
+
             match &end_date {
                 Some(ed) => {
                     let end_run = chrono::NaiveDateTime::parse_from_str(
                         &format!("{} {}", ed, time),
                         &format!("{} {}", CALENDAR_DEFAULT_DATE_FORMAT, TIME_DEFAULT_FORMAT),
                     )?;
-                    let end_run = end_run
-                        .and_local_timezone(Sarajevo)
-                        .single()
-                        .ok_or("Failed to convert to timezone-aware datetime")?
-                        .with_timezone(&chrono::Utc);
+                    let end_run = resolve_local_datetime(&Sarajevo, end_run);
 
-                    scheduler
-                        .add_task(Task::new_with_datetime_range(next_run, end_run, action))
-                        .await?;
+                    let mut task = Task::new_with_datetime_range(next_run, end_run, action)
+                        .with_timezone(Sarajevo)
+                        .with_metadata(metadata);
+
+                    if let Some(interval) = &interval {
+                        let delay = parse_interval(interval)
+                            .ok_or_else(|| format!("Invalid interval: {}", interval))?;
+                        task = task.with_delay_between_runs(delay);
+                    }
+
+                    scheduler.add_task(task).await?;
                 }
                 None => {
                     scheduler
-                        .add_task(Task::new_with_datetime(next_run, action))
+                        .add_task(
+                            Task::new_with_datetime(next_run, action)
+                                .with_timezone(Sarajevo)
+                                .with_metadata(metadata),
+                        )
                         .await?;
                 }
             }
@@ -112,12 +129,16 @@ pub async fn handle_assigne_mention_callback(
 
             let confirmation_message = match &end_date {
                 Some(ed) => format!(
-                    "Zadatak '{}' je dodijeljen bratu {} od {} do {} u {} svaki dan\\.",
+                    "Zadatak '{}' je dodijeljen bratu {} od {} do {} u {} {}\\.",
                     task_name,
                     mention,
                     date,
                     markdown::escape(ed),
-                    time
+                    time,
+                    interval
+                        .as_deref()
+                        .and_then(get_interval_label)
+                        .unwrap_or("svaki dan")
                 ),
                 None => format!(
                     "Zadatak '{}' je dodijeljen bratu {} za {} u {}\\.",
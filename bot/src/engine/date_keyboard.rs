@@ -1,4 +1,5 @@
 use chrono::{Datelike, NaiveDate};
+use scheduler::clock::Clock;
 use teloxide::{
     Bot,
     payloads::{EditMessageReplyMarkupSetters, SendMessageSetters},
@@ -153,9 +154,10 @@ pub async fn handle_keyboard_date_selection(
     dialogue: TaskDialogue,
     state: TaskState,
     date: NaiveDate,
+    clock: &dyn Clock,
 ) -> ChatHandlerResult {
-    let min_time = if date == get_current_date_in_bosnia() {
-        Some(get_current_time_in_bosnia())
+    let min_time = if date == get_current_date_in_bosnia(clock) {
+        Some(get_current_time_in_bosnia(clock))
     } else {
         None
     };
@@ -173,7 +175,7 @@ pub async fn handle_keyboard_date_selection(
                 .await?;
         }
         TaskState::AwaitingRangeStartDate { task_name } => {
-            let now = get_current_date_in_bosnia();
+            let now = get_current_date_in_bosnia(clock);
             let start_date = date.format(CALENDAR_DEFAULT_DATE_FORMAT).to_string();
             let date_plus_one_day = date + chrono::Duration::days(1);
 
@@ -216,6 +218,7 @@ pub async fn handle_keyboard_calendar_navigation(
     bot: Bot,
     q: &CallbackQuery,
     data: &str,
+    clock: &dyn Clock,
 ) -> ChatHandlerResult {
     let parts: Vec<&str> = data.split('_').collect();
     if parts.len() >= 4 {
@@ -239,7 +242,7 @@ pub async fn handle_keyboard_calendar_navigation(
                 .reply_markup(create_calendar_keyboard(
                     new_year,
                     new_month,
-                    Some(chrono::Utc::now().date_naive()),
+                    Some(clock.now().date_naive()),
                 ))
                 .await?;
         }
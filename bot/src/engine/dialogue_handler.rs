@@ -1,5 +1,7 @@
+use std::sync::Arc;
+
 use chrono::{Datelike, NaiveDate};
-use scheduler::task::task_scheduler::TaskScheduler;
+use scheduler::{clock::Clock, task::task_scheduler::TaskScheduler};
 use teloxide::{
     Bot,
     dispatching::{DpHandlerDescription, HandlerExt, UpdateFilterExt, dialogue::InMemStorage},
@@ -16,6 +18,10 @@ use crate::engine::{
         CALENDAR_CALLBACK_SELECT_PREFIX, create_calendar_keyboard,
         handle_keyboard_calendar_navigation, handle_keyboard_date_selection,
     },
+    interval_keyboard::{
+        INTERVAL_SELECTION_CALLBACK_PREFIX, INTERVAL_SELECTION_CANCEL,
+        handle_keyboard_interval_selection,
+    },
     time_keyboard::{
         TIME_SELECTION_CALLBACK_PREFIX, TIME_SELECTION_CANCEL, TIME_SELECTION_PAGE_PREFIX,
         create_time_selection_keyboard, handle_keyboard_time_selection,
@@ -54,11 +60,18 @@ pub enum TaskState {
         start_date: String,
         end_date: String,
     },
+    AwaitingRangeInterval {
+        task_name: String,
+        start_date: String,
+        end_date: String,
+        time: String,
+    },
     AwaitingAssigneeMention {
         task_name: String,
         date: String,
         end_date: Option<String>,
         time: String,
+        interval: Option<String>,
     },
 }
 
@@ -88,6 +101,7 @@ pub fn build_dialogue_handler(
                 date,
                 time,
                 end_date,
+                interval,
             }]
             .endpoint(move |bot, msg, dialogue| {
                 let scheduler = scheduler.clone();
@@ -96,14 +110,19 @@ pub fn build_dialogue_handler(
         )
 }
 
-pub fn build_dialogue_callback_handler() -> Handler<
+pub fn build_dialogue_callback_handler(
+    clock: Arc<dyn Clock>,
+) -> Handler<
     'static,
     Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>,
     DpHandlerDescription,
 > {
     Update::filter_callback_query()
         .enter_dialogue::<CallbackQuery, InMemStorage<TaskState>, TaskState>()
-        .endpoint(handle_dialogue_callback)
+        .endpoint(move |bot, q, dialogue| {
+            let clock = Arc::clone(&clock);
+            async move { handle_dialogue_callback(bot, q, dialogue, clock.as_ref()).await }
+        })
 }
 
 async fn handle_task_name_callback(
@@ -130,6 +149,7 @@ async fn handle_dialogue_callback(
     bot: Bot,
     q: CallbackQuery,
     dialogue: TaskDialogue,
+    clock: &dyn Clock,
 ) -> ChatHandlerResult {
     if let Some(data) = &q.data {
         let chat_id = q
@@ -142,7 +162,7 @@ async fn handle_dialogue_callback(
         match data.as_str() {
             s if s == TASK_TYPE_SPECIFIC_ID => {
                 if let TaskState::AwaitingTaskType { task_name } = state {
-                    let now = get_current_date_in_bosnia();
+                    let now = get_current_date_in_bosnia(clock);
 
                     remove_keyboard_buttons(&bot, &q).await;
                     bot.send_message(chat_id, "Odaberi datum za zadatak:")
@@ -155,7 +175,7 @@ async fn handle_dialogue_callback(
             }
             s if s == TASK_TYPE_RECURRING_ID => {
                 if let TaskState::AwaitingTaskType { task_name } = state {
-                    let now = get_current_date_in_bosnia();
+                    let now = get_current_date_in_bosnia(clock);
 
                     remove_keyboard_buttons(&bot, &q).await;
                     bot.send_message(chat_id, "Odaberi početni datum za ponavljajući zadatak:")
@@ -171,8 +191,15 @@ async fn handle_dialogue_callback(
                 if let Ok(date) = NaiveDate::parse_from_str(date_str, CALENDAR_DEFAULT_DATE_FORMAT)
                 {
                     remove_keyboard_buttons(&bot, &q).await;
-                    handle_keyboard_date_selection(bot.clone(), chat_id, dialogue, state, date)
-                        .await?;
+                    handle_keyboard_date_selection(
+                        bot.clone(),
+                        chat_id,
+                        dialogue,
+                        state,
+                        date,
+                        clock,
+                    )
+                    .await?;
                 }
             }
             s if s.starts_with(TIME_SELECTION_CALLBACK_PREFIX) => {
@@ -181,14 +208,21 @@ async fn handle_dialogue_callback(
                 handle_keyboard_time_selection(bot.clone(), chat_id, dialogue, state, time_str)
                     .await?;
             }
+            s if s.starts_with(INTERVAL_SELECTION_CALLBACK_PREFIX) => {
+                let interval = s.trim_start_matches(INTERVAL_SELECTION_CALLBACK_PREFIX);
+                remove_keyboard_buttons(&bot, &q).await;
+                handle_keyboard_interval_selection(bot.clone(), chat_id, dialogue, state, interval)
+                    .await?;
+            }
             s if s.starts_with(CALENDAR_CALLBACK_PREV_PREFIX)
                 || s.starts_with(CALENDAR_CALLBACK_NEXT_PREFIX) =>
             {
-                handle_keyboard_calendar_navigation(bot.clone(), &q, s).await?;
+                handle_keyboard_calendar_navigation(bot.clone(), &q, s, clock).await?;
             }
             s if s == TASK_TYPE_CANCEL_ID
                 || s == CALENDAR_CALLBACK_CANCEL
-                || s == TIME_SELECTION_CANCEL =>
+                || s == TIME_SELECTION_CANCEL
+                || s == INTERVAL_SELECTION_CANCEL =>
             {
                 remove_keyboard_buttons(&bot, &q).await;
                 bot.send_message(chat_id, "Zakazivanje zadatka je otkazano.")
@@ -218,9 +252,9 @@ async fn handle_dialogue_callback(
                         if let Some(ds) = date_str {
                             let task_date =
                                 NaiveDate::parse_from_str(&ds, CALENDAR_DEFAULT_DATE_FORMAT)
-                                    .unwrap_or(get_current_date_in_bosnia());
-                            if task_date == get_current_date_in_bosnia() {
-                                Some(get_current_time_in_bosnia())
+                                    .unwrap_or(get_current_date_in_bosnia(clock));
+                            if task_date == get_current_date_in_bosnia(clock) {
+                                Some(get_current_time_in_bosnia(clock))
                             } else {
                                 None
                             }
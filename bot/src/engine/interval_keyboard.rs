@@ -0,0 +1,109 @@
+use teloxide::{
+    Bot,
+    prelude::Requester,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::engine::{
+    dialogue_handler::{TaskDialogue, TaskState},
+    utils::ChatHandlerResult,
+};
+
+pub static INTERVAL_SELECTION_CALLBACK_PREFIX: &str = "interval_select_";
+pub static INTERVAL_SELECTION_CANCEL: &str = "interval_cancel";
+
+/// Selectable intervals, encoded as `<amount><unit>` where the unit is either `h` (hours)
+/// or `d` (days), together with the label shown to the user.
+pub static INTERVAL_OPTIONS: &[(&str, &str)] = &[
+    ("1h", "svaki sat"),
+    ("1d", "svaki dan"),
+    ("2d", "svaka 2 dana"),
+    ("3d", "svaka 3 dana"),
+    ("7d", "svake sedmice"),
+];
+
+pub fn create_interval_selection_keyboard() -> InlineKeyboardMarkup {
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = INTERVAL_OPTIONS
+        .chunks(2)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|(interval, label)| {
+                    InlineKeyboardButton::callback(
+                        format!("🔄 {}", label),
+                        format!("{}{}", INTERVAL_SELECTION_CALLBACK_PREFIX, interval),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    rows.push(vec![InlineKeyboardButton::callback(
+        "❌ Odustani",
+        INTERVAL_SELECTION_CANCEL,
+    )]);
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+pub fn parse_interval(interval: &str) -> Option<chrono::Duration> {
+    if let Some(hours) = interval.strip_suffix('h') {
+        hours
+            .parse()
+            .ok()
+            .filter(|h| *h > 0)
+            .map(chrono::Duration::hours)
+    } else if let Some(days) = interval.strip_suffix('d') {
+        days.parse()
+            .ok()
+            .filter(|d| *d > 0)
+            .map(chrono::Duration::days)
+    } else {
+        None
+    }
+}
+
+pub fn get_interval_label(interval: &str) -> Option<&'static str> {
+    INTERVAL_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == interval)
+        .map(|(_, label)| *label)
+}
+
+pub async fn handle_keyboard_interval_selection(
+    bot: Bot,
+    chat_id: ChatId,
+    dialogue: TaskDialogue,
+    state: TaskState,
+    interval: &str,
+) -> ChatHandlerResult {
+    if let TaskState::AwaitingRangeInterval {
+        task_name,
+        start_date,
+        end_date,
+        time,
+    } = state
+    {
+        if parse_interval(interval).is_none() {
+            return Err(format!("Invalid interval: {}", interval).into());
+        }
+
+        bot.send_message(
+            chat_id,
+            "Označi korisnika kojem želiš dodijeliti zadatak, brate (npr. @korisnik):",
+        )
+        .await?;
+
+        dialogue
+            .update(TaskState::AwaitingAssigneeMention {
+                task_name,
+                date: start_date,
+                time,
+                end_date: Some(end_date),
+                interval: Some(interval.to_string()),
+            })
+            .await?;
+    }
+
+    Ok(())
+}
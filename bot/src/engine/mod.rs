@@ -5,5 +5,6 @@ mod command;
 pub mod command_handler;
 pub mod date_keyboard;
 pub mod dialogue_handler;
+pub mod interval_keyboard;
 pub mod time_keyboard;
 pub mod utils;
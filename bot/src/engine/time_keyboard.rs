@@ -1,12 +1,14 @@
 use chrono::{NaiveTime, Timelike};
 use teloxide::{
     Bot,
+    payloads::SendMessageSetters,
     prelude::Requester,
     types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup},
 };
 
 use crate::engine::{
     dialogue_handler::{TaskDialogue, TaskState},
+    interval_keyboard::create_interval_selection_keyboard,
     utils::ChatHandlerResult,
 };
 
@@ -90,20 +92,20 @@ pub async fn handle_keyboard_time_selection(
     state: TaskState,
     time_str: &str,
 ) -> ChatHandlerResult {
-    bot.send_message(
-        chat_id,
-        "Označi korisnika kojem želiš dodijeliti zadatak, brate (npr. @korisnik):",
-    )
-    .await?;
-
     match state {
         TaskState::AwaitingSpecificTime { task_name, date } => {
+            bot.send_message(
+                chat_id,
+                "Označi korisnika kojem želiš dodijeliti zadatak, brate (npr. @korisnik):",
+            )
+            .await?;
             dialogue
                 .update(TaskState::AwaitingAssigneeMention {
                     task_name,
                     date,
                     time: time_str.to_string(),
                     end_date: None,
+                    interval: None,
                 })
                 .await?;
         }
@@ -112,12 +114,18 @@ pub async fn handle_keyboard_time_selection(
             start_date,
             end_date,
         } => {
+            bot.send_message(
+                chat_id,
+                "Odaberi koliko često želiš da se zadatak ponavlja:",
+            )
+            .reply_markup(create_interval_selection_keyboard())
+            .await?;
             dialogue
-                .update(TaskState::AwaitingAssigneeMention {
+                .update(TaskState::AwaitingRangeInterval {
                     task_name,
-                    date: start_date,
+                    start_date,
+                    end_date,
                     time: time_str.to_string(),
-                    end_date: Some(end_date),
                 })
                 .await?;
         }
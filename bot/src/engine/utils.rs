@@ -1,4 +1,5 @@
 use chrono::{NaiveDate, TimeZone};
+use scheduler::clock::Clock;
 use teloxide::{
     Bot,
     payloads::SendMessageSetters,
@@ -55,15 +56,15 @@ pub fn get_current_user_mention(msg: &Message) -> Option<String> {
     None
 }
 
-pub fn get_current_date_in_bosnia() -> NaiveDate {
+pub fn get_current_date_in_bosnia(clock: &dyn Clock) -> NaiveDate {
     use chrono_tz::Europe::Sarajevo;
-    let now_in_tz = Sarajevo.from_utc_datetime(&chrono::Utc::now().naive_utc());
+    let now_in_tz = Sarajevo.from_utc_datetime(&clock.now().naive_utc());
     now_in_tz.date_naive()
 }
 
-pub fn get_current_time_in_bosnia() -> chrono::NaiveTime {
+pub fn get_current_time_in_bosnia(clock: &dyn Clock) -> chrono::NaiveTime {
     use chrono_tz::Europe::Sarajevo;
-    let now_in_tz = Sarajevo.from_utc_datetime(&chrono::Utc::now().naive_utc());
+    let now_in_tz = Sarajevo.from_utc_datetime(&clock.now().naive_utc());
     now_in_tz.time()
 }
 
@@ -1,34 +1,71 @@
 use scheduler::{
     db::migrator::Migrator,
-    storage::database_storage::DatabaseStorage,
+    storage::{
+        base_storage::Storage, database_storage::DatabaseStorage, file_storage::FileStorage,
+        redis_storage::RedisStorage, sqlite_storage::SqliteStorage,
+    },
     task::{
-        action_registry::ActionRegistry, log_executor::LogExecutor, task_scheduler::TaskScheduler,
+        action::{ActionType, TaskAction},
+        action_registry::ActionRegistry,
+        log_executor::LogExecutor,
+        task_scheduler::TaskScheduler,
     },
 };
 use std::sync::Arc;
 use teloxide::Bot;
 
-use crate::{bot_executor::BotExecutor, engine::chat_engine::ChatEngine};
+use crate::{
+    bot_executor::{BotExecutor, dead_letter_message},
+    engine::chat_engine::ChatEngine,
+};
 
 mod bot_executor;
 mod engine;
 
+/// Creates the storage selected by the `STORAGE` variable and migrates it.
+async fn create_storage() -> Result<Arc<dyn Storage>, Box<dyn std::error::Error>> {
+    let storage = std::env::var("STORAGE").unwrap_or("postgres".to_string());
+
+    match storage.as_str() {
+        "postgres" => {
+            let database_url = format!(
+                "postgres://{}:{}@{}:{}/{}",
+                std::env::var("DB_USER").unwrap_or("postgres".to_string()),
+                std::env::var("DB_PASSWORD").unwrap_or("postgres".to_string()),
+                std::env::var("DB_HOST").unwrap_or("localhost".to_string()),
+                std::env::var("DB_PORT").unwrap_or("5432".to_string()),
+                std::env::var("DB_NAME").unwrap_or("wt_db".to_string())
+            );
+
+            let db_storage = DatabaseStorage::new(&database_url).await?;
+            Migrator::run(&database_url).await?;
+            Ok(Arc::new(db_storage))
+        }
+        "sqlite" => {
+            let database_url =
+                std::env::var("SQLITE_URL").unwrap_or("sqlite://wt_db.sqlite".to_string());
+
+            Migrator::run(&database_url).await?;
+            Ok(Arc::new(SqliteStorage::new(&database_url).await?))
+        }
+        "file" => {
+            let data_dir = std::env::var("DATA_DIR").unwrap_or("data".to_string());
+            Ok(Arc::new(FileStorage::new(data_dir).await?))
+        }
+        "redis" => {
+            let redis_url =
+                std::env::var("REDIS_URL").unwrap_or("redis://localhost:6379".to_string());
+            Ok(Arc::new(RedisStorage::new(&redis_url).await?))
+        }
+        other => Err(format!("Unknown storage: {}", other).into()),
+    }
+}
+
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     pretty_env_logger::init();
 
-    let database_url = format!(
-        "postgres://{}:{}@{}:{}/{}",
-        std::env::var("DB_USER").unwrap_or("postgres".to_string()),
-        std::env::var("DB_PASSWORD").unwrap_or("postgres".to_string()),
-        std::env::var("DB_HOST").unwrap_or("localhost".to_string()),
-        std::env::var("DB_PORT").unwrap_or("5432".to_string()),
-        std::env::var("DB_NAME").unwrap_or("wt_db".to_string())
-    );
-
-    let db_storage = DatabaseStorage::new(&database_url).await?;
-
-    Migrator::run(&database_url).await?;
+    let storage = create_storage().await?;
 
     let bot = Bot::from_env();
 
@@ -36,14 +73,28 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     registry.register(LogExecutor::new());
     registry.register(BotExecutor::new(bot.clone()));
 
-    let scheduler = TaskScheduler::new(Arc::new(db_storage), registry);
-    scheduler.start().await?;
-    let handle = scheduler.shutdown_on_ctrl_c();
+    // Keep a backlog of overdue reminders from flooding Telegram.
+    let mut scheduler = TaskScheduler::new(storage, registry)
+        .with_action_concurrency(ActionType::SendBotMessage, 10);
+
+    if let Some(admin_chat_id) = std::env::var("ADMIN_CHAT_ID")
+        .ok()
+        .and_then(|chat_id| chat_id.parse::<i64>().ok())
+    {
+        scheduler =
+            scheduler.with_dead_letter_action(move |dead_letter| TaskAction::SendBotMessage {
+                chat_id: admin_chat_id,
+                message: dead_letter_message(dead_letter),
+            });
+    }
+    let scheduler_handle = scheduler.start().await?;
+    let ctrl_c_handle = scheduler.shutdown_on_ctrl_c();
 
     let chat_engine = ChatEngine::new(bot, scheduler);
     chat_engine.run().await?;
 
-    handle.await??;
+    ctrl_c_handle.await??;
+    scheduler_handle.await?;
 
     Ok(())
 }
@@ -0,0 +1,62 @@
+use bot::bot_executor::dead_letter_message;
+use scheduler::{
+    error::SchedulerError,
+    task::{action::TaskAction, dead_letter::DeadLetter, default::Task, metadata::TaskMetadata},
+};
+
+fn create_task() -> Task {
+    let action = TaskAction::SendBotMessage {
+        chat_id: 1,
+        message: "Reminder".to_string(),
+    };
+    Task::new_with_datetime(chrono::Utc::now(), action)
+}
+
+#[test]
+fn test_dead_letter_message_escapes_markdown() {
+    let task = create_task().with_metadata(TaskMetadata {
+        title: Some("Kupi mlijeko (2 l).".to_string()),
+        ..Default::default()
+    });
+    let error = SchedulerError::TaskExecutionError("Request failed (timeout).".to_string());
+    let dead_letter = DeadLetter::new(task.clone(), &error, chrono::Utc::now());
+
+    let message = dead_letter_message(&dead_letter);
+
+    assert!(
+        message.contains("'Kupi mlijeko \\(2 l\\)\\.'"),
+        "Message should name the task by its escaped title. Got: {}",
+        message
+    );
+    assert!(
+        message.contains(&format!(
+            "\\({}\\)",
+            task.id.to_string().replace('-', "\\-")
+        )),
+        "Message should contain the escaped task id. Got: {}",
+        message
+    );
+    assert!(
+        message.contains("Request failed \\(timeout\\)\\."),
+        "Message should contain the escaped error. Got: {}",
+        message
+    );
+}
+
+#[test]
+fn test_dead_letter_message_falls_back_to_task_id() {
+    let task = create_task();
+    let error = SchedulerError::TaskExecutionError("Failed".to_string());
+    let dead_letter = DeadLetter::new(task.clone(), &error, chrono::Utc::now());
+
+    let message = dead_letter_message(&dead_letter);
+
+    assert!(
+        message.contains(&format!(
+            "Zadatak {} nije",
+            task.id.to_string().replace('-', "\\-")
+        )),
+        "Message should name the task by its escaped id. Got: {}",
+        message
+    );
+}
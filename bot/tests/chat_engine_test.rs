@@ -274,6 +274,7 @@ async fn test_assigne_mention_task_has_correct_action() {
         date: "15.03.2030".to_string(),
         time: "10:00".to_string(),
         end_date: None,
+        interval: None,
     })
     .await;
     bot.dispatch().await;
@@ -301,6 +302,41 @@ async fn test_assigne_mention_task_has_correct_action() {
     }
 }
 
+#[tokio::test]
+async fn test_assigne_mention_task_has_metadata() {
+    let (scheduler, storage, _) = create_test_scheduler_with_storage();
+
+    let message = MockMessageText::new()
+        .text("Hello there @user")
+        .entities(vec![create_mention_entity(12, 5)]);
+    let chat_id = message.chat.id.0;
+    let handler = build_dialogue_handler(scheduler);
+
+    let mut bot = MockBot::new(message, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingAssigneeMention {
+        task_name: "Doctor Appointment".to_string(),
+        date: "15.03.2030".to_string(),
+        time: "10:00".to_string(),
+        end_date: None,
+        interval: None,
+    })
+    .await;
+    bot.dispatch().await;
+
+    let tasks = storage.get_all_tasks().await.unwrap();
+    assert_eq!(tasks.len(), 1, "One task should be created");
+
+    let metadata = &tasks[0].metadata;
+    assert_eq!(metadata.title.as_deref(), Some("Doctor Appointment"));
+    assert_eq!(metadata.assignee.as_deref(), Some("@user"));
+    assert_eq!(metadata.chat_id, Some(chat_id));
+    assert!(
+        metadata.created_by.is_some(),
+        "Task should record its creator"
+    );
+}
+
 #[tokio::test]
 async fn test_assignee_mention_with_tg_link_has_correct_action() {
     let (scheduler, storage, _) = create_test_scheduler_with_storage();
@@ -332,6 +368,7 @@ async fn test_assignee_mention_with_tg_link_has_correct_action() {
         date: "20.04.2030".to_string(),
         time: "15:30".to_string(),
         end_date: None,
+        interval: None,
     })
     .await;
     bot.dispatch().await;
@@ -390,6 +427,7 @@ async fn test_assignee_mention_with_tg_handles_special_chars() {
         date: "25.05.2030".to_string(),
         time: "09:00".to_string(),
         end_date: None,
+        interval: None,
     })
     .await;
     bot.dispatch().await;
@@ -431,6 +469,7 @@ async fn test_assignee_mention_without_mention_asks_for_mention() {
         date: "30.06.2030".to_string(),
         time: "12:00".to_string(),
         end_date: None,
+        interval: None,
     })
     .await;
     bot.dispatch().await;
@@ -463,6 +502,7 @@ async fn test_assignee_mention_with_datetime_range() {
         date: "01.07.2030".to_string(),
         time: "09:00".to_string(),
         end_date: Some("03.07.2030".to_string()),
+        interval: None,
     })
     .await;
     bot.dispatch().await;
@@ -489,3 +529,41 @@ async fn test_assignee_mention_with_datetime_range() {
         panic!("Task action should be SendBotMessage");
     }
 }
+
+#[tokio::test]
+async fn test_assignee_mention_with_datetime_range_and_interval() {
+    let (scheduler, storage, _) = create_test_scheduler_with_storage();
+
+    let message = MockMessageText::new()
+        .text("Hello there @user")
+        .entities(vec![create_mention_entity(12, 5)]);
+    let handler = build_dialogue_handler(scheduler);
+
+    let mut bot = MockBot::new(message, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingAssigneeMention {
+        task_name: "Weekly Sync".to_string(),
+        date: "01.07.2030".to_string(),
+        time: "09:00".to_string(),
+        end_date: Some("30.09.2030".to_string()),
+        interval: Some("7d".to_string()),
+    })
+    .await;
+    bot.dispatch().await;
+
+    let tasks = storage.get_all_tasks().await.unwrap();
+    assert_eq!(tasks.len(), 1, "One task should be created");
+    assert_eq!(
+        tasks[0].delay_between_runs,
+        Some(chrono::Duration::days(7)),
+        "Task should repeat weekly"
+    );
+
+    let responses = bot.get_responses();
+    let last_message_text = responses.sent_messages.last().unwrap().text().unwrap();
+    assert!(
+        last_message_text.contains("svake sedmice"),
+        "Confirmation should mention the interval. Got: {}",
+        last_message_text
+    );
+}
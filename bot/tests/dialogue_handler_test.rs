@@ -1,5 +1,7 @@
 mod common;
 
+use std::sync::Arc;
+
 use bot::engine::date_keyboard::{
     CALENDAR_CALLBACK_CANCEL, CALENDAR_CALLBACK_IGNORE, CALENDAR_CALLBACK_NEXT_PREFIX,
     CALENDAR_CALLBACK_PREV_PREFIX, CALENDAR_CALLBACK_SELECT_PREFIX, create_calendar_keyboard,
@@ -7,13 +9,17 @@ use bot::engine::date_keyboard::{
 use bot::engine::dialogue_handler::{
     TaskState, build_dialogue_callback_handler, build_dialogue_handler,
 };
+use bot::engine::interval_keyboard::{
+    INTERVAL_SELECTION_CALLBACK_PREFIX, INTERVAL_SELECTION_CANCEL, parse_interval,
+};
 use bot::engine::time_keyboard::{
     TIME_SELECTION_CALLBACK_PREFIX, TIME_SELECTION_CANCEL, create_time_selection_keyboard,
 };
 use bot::engine::utils::{TASK_TYPE_CANCEL_ID, TASK_TYPE_RECURRING_ID, TASK_TYPE_SPECIFIC_ID};
-use chrono::{NaiveDate, NaiveTime, Utc};
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
 use common::create_test_scheduler_with_storage;
 use dptree::deps;
+use scheduler::clock::{ManualClock, SystemClock};
 use scheduler::storage::base_storage::Storage;
 use scheduler::task::action::TaskAction;
 use scheduler::task::default::Task;
@@ -61,7 +67,7 @@ async fn test_receive_task_name_transitions_to_awaiting_type() {
 #[tokio::test]
 async fn test_task_type_specific_transitions_to_date() {
     let callback = MockCallbackQuery::new().data(TASK_TYPE_SPECIFIC_ID);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -93,7 +99,7 @@ async fn test_task_type_specific_transitions_to_date() {
 #[tokio::test]
 async fn test_task_type_recurring_transitions_to_start_date() {
     let callback = MockCallbackQuery::new().data(TASK_TYPE_RECURRING_ID);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -127,7 +133,7 @@ async fn test_date_selection_transitions_to_time() {
     // Use a future date to avoid filtering issues
     let date_callback = format!("{}01.01.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
     let callback = MockCallbackQuery::new().data(&date_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -163,7 +169,7 @@ async fn test_date_selection_transitions_to_time() {
 #[tokio::test]
 async fn test_cancel_from_task_type_exits() {
     let callback = MockCallbackQuery::new().data(TASK_TYPE_CANCEL_ID);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -194,7 +200,7 @@ async fn test_cancel_from_task_type_exits() {
 #[tokio::test]
 async fn test_cancel_from_calendar_exits() {
     let callback = MockCallbackQuery::new().data(CALENDAR_CALLBACK_CANCEL);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -225,7 +231,7 @@ async fn test_cancel_from_calendar_exits() {
 #[tokio::test]
 async fn test_cancel_from_time_selection_exits() {
     let callback = MockCallbackQuery::new().data(TIME_SELECTION_CANCEL);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -263,7 +269,7 @@ async fn test_calendar_prev_month_updates_keyboard() {
     // Navigate from December 2025 to November 2025
     let nav_callback = format!("{}2025_12", CALENDAR_CALLBACK_PREV_PREFIX);
     let callback = MockCallbackQuery::new().data(&nav_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -288,7 +294,7 @@ async fn test_calendar_next_month_updates_keyboard() {
     // Navigate from December 2025 to January 2026
     let nav_callback = format!("{}2025_12", CALENDAR_CALLBACK_NEXT_PREFIX);
     let callback = MockCallbackQuery::new().data(&nav_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -315,7 +321,7 @@ async fn test_calendar_next_month_updates_keyboard() {
 async fn test_recurring_start_date_transitions_to_end_date() {
     let date_callback = format!("{}01.01.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
     let callback = MockCallbackQuery::new().data(&date_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -347,7 +353,7 @@ async fn test_recurring_start_date_transitions_to_end_date() {
 async fn test_recurring_end_date_transitions_to_time() {
     let date_callback = format!("{}15.01.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
     let callback = MockCallbackQuery::new().data(&date_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -386,7 +392,7 @@ async fn test_callback_in_wrong_state_is_handled() {
 
     // Try to select a task type when in Idle state (wrong state)
     let callback = MockCallbackQuery::new().data(TASK_TYPE_SPECIFIC_ID);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -430,7 +436,7 @@ async fn test_task_name_with_special_characters() {
 async fn test_date_selection_preserves_task_name_in_state() {
     let date_callback = format!("{}15.06.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
     let callback = MockCallbackQuery::new().data(&date_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -460,7 +466,7 @@ async fn test_recurring_end_date_after_start_date() {
     // End date is after start date
     let date_callback = format!("{}20.01.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
     let callback = MockCallbackQuery::new().data(&date_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -486,6 +492,39 @@ async fn test_recurring_end_date_after_start_date() {
     );
 }
 
+#[tokio::test]
+async fn test_date_selection_of_today_filters_past_times_in_bosnia() {
+    // 22:30 UTC is already 23:30 in Sarajevo
+    let clock = Arc::new(ManualClock::new(
+        Utc.with_ymd_and_hms(2030, 1, 1, 22, 30, 0).unwrap(),
+    ));
+    let date_callback = format!("{}01.01.2030", CALENDAR_CALLBACK_SELECT_PREFIX);
+    let callback = MockCallbackQuery::new().data(&date_callback);
+    let handler = build_dialogue_callback_handler(clock);
+
+    let mut bot = MockBot::new(callback, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingSpecificDate {
+        task_name: "Late Reminder".to_string(),
+    })
+    .await;
+    bot.dispatch().await;
+
+    let responses = bot.get_responses();
+    let keyboard = responses
+        .sent_messages
+        .last()
+        .and_then(|message| message.reply_markup())
+        .expect("Time selection should include keyboard");
+
+    assert_eq!(
+        count_time_slots(keyboard),
+        2,
+        "Should have exactly 2 slots (23:30 and 23:45)"
+    );
+    assert_eq!(get_first_time_slot(keyboard), Some("23:30".to_string()));
+}
+
 // =============================================================================
 // handle_time_selection Tests
 // =============================================================================
@@ -494,7 +533,7 @@ async fn test_recurring_end_date_after_start_date() {
 async fn test_time_selection_confirmation_contains_details() {
     let time_callback = format!("{}16:45", TIME_SELECTION_CALLBACK_PREFIX);
     let callback = MockCallbackQuery::new().data(&time_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -524,7 +563,7 @@ async fn test_time_selection_in_wrong_state_does_nothing() {
     // Try time selection when in AwaitingTaskType state (wrong state)
     let time_callback = format!("{}12:00", TIME_SELECTION_CALLBACK_PREFIX);
     let callback = MockCallbackQuery::new().data(&time_callback);
-    let handler = build_dialogue_callback_handler();
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
 
     let mut bot = MockBot::new(callback, handler);
     bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
@@ -542,6 +581,111 @@ async fn test_time_selection_in_wrong_state_does_nothing() {
     );
 }
 
+// =============================================================================
+// Interval Selection Tests
+// =============================================================================
+
+#[tokio::test]
+async fn test_recurring_time_selection_transitions_to_interval() {
+    let time_callback = format!("{}08:30", TIME_SELECTION_CALLBACK_PREFIX);
+    let callback = MockCallbackQuery::new().data(&time_callback);
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
+
+    let mut bot = MockBot::new(callback, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingRangeTime {
+        task_name: "Daily Standup".to_string(),
+        start_date: "01.01.2030".to_string(),
+        end_date: "20.01.2030".to_string(),
+    })
+    .await;
+    bot.dispatch().await;
+
+    let responses = bot.get_responses();
+    let last_message = responses.sent_messages.last().unwrap();
+    let text = last_message.text().unwrap_or("");
+
+    assert!(
+        text.contains("koliko često"),
+        "Should ask for the interval after time selection. Got: {}",
+        text
+    );
+
+    let has_interval_buttons = last_message
+        .reply_markup()
+        .map(|keyboard| {
+            keyboard.inline_keyboard.iter().flatten().any(|btn| {
+                get_callback_data(btn)
+                    .map(|d| d.starts_with(INTERVAL_SELECTION_CALLBACK_PREFIX))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false);
+    assert!(has_interval_buttons, "Interval keyboard should be attached");
+}
+
+#[tokio::test]
+async fn test_interval_selection_transitions_to_assignee() {
+    let interval_callback = format!("{}2d", INTERVAL_SELECTION_CALLBACK_PREFIX);
+    let callback = MockCallbackQuery::new().data(&interval_callback);
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
+
+    let mut bot = MockBot::new(callback, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingRangeInterval {
+        task_name: "Daily Standup".to_string(),
+        start_date: "01.01.2030".to_string(),
+        end_date: "20.01.2030".to_string(),
+        time: "08:30".to_string(),
+    })
+    .await;
+    bot.dispatch().await;
+
+    let responses = bot.get_responses();
+    let text = responses.sent_messages.last().unwrap().text().unwrap_or("");
+
+    assert!(
+        text.contains("Označi korisnika kojem želiš dodijeliti zadatak"),
+        "Should ask for the assignee after interval selection. Got: {}",
+        text
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_from_interval_selection_exits() {
+    let callback = MockCallbackQuery::new().data(INTERVAL_SELECTION_CANCEL);
+    let handler = build_dialogue_callback_handler(Arc::new(SystemClock));
+
+    let mut bot = MockBot::new(callback, handler);
+    bot.dependencies(deps![InMemStorage::<TaskState>::new()]);
+    bot.set_state(TaskState::AwaitingRangeInterval {
+        task_name: "Daily Standup".to_string(),
+        start_date: "01.01.2030".to_string(),
+        end_date: "20.01.2030".to_string(),
+        time: "08:30".to_string(),
+    })
+    .await;
+    bot.dispatch().await;
+
+    let responses = bot.get_responses();
+    let text = responses.sent_messages.last().unwrap().text().unwrap_or("");
+
+    assert!(
+        text.contains("otkazano"),
+        "Response should confirm cancellation. Got: {}",
+        text
+    );
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(parse_interval("1h"), Some(chrono::Duration::hours(1)));
+    assert_eq!(parse_interval("3d"), Some(chrono::Duration::days(3)));
+    assert_eq!(parse_interval("7d"), Some(chrono::Duration::days(7)));
+    assert_eq!(parse_interval("0d"), None);
+    assert_eq!(parse_interval("weekly"), None);
+}
+
 // =============================================================================
 // Time Keyboard Filtering Tests
 // =============================================================================
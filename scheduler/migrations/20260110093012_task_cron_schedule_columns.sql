@@ -0,0 +1,5 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN cron_expression TEXT,
+ADD COLUMN timezone TEXT;
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN delay_between_runs BIGINT; -- in milliseconds
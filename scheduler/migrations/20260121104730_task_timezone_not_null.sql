@@ -0,0 +1,7 @@
+-- Add migration script here
+
+UPDATE tasks SET timezone = 'UTC' WHERE timezone IS NULL;
+
+ALTER TABLE tasks
+ALTER COLUMN timezone SET DEFAULT 'UTC',
+ALTER COLUMN timezone SET NOT NULL;
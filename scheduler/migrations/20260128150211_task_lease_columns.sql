@@ -0,0 +1,7 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN locked_by TEXT,
+ADD COLUMN locked_until TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON tasks (next_run) WHERE enabled = TRUE;
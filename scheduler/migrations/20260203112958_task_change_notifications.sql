@@ -0,0 +1,23 @@
+-- Add migration script here
+
+CREATE OR REPLACE FUNCTION notify_tasks_changed() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('tasks_changed', COALESCE(NEW.id, OLD.id)::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_inserted_or_deleted
+AFTER INSERT OR DELETE ON tasks
+FOR EACH ROW EXECUTE FUNCTION notify_tasks_changed();
+
+-- Claiming a task only takes a lease, which doesn't make it due any sooner for others.
+CREATE TRIGGER tasks_updated
+AFTER UPDATE ON tasks
+FOR EACH ROW
+WHEN (
+    OLD.next_run IS DISTINCT FROM NEW.next_run
+    OR OLD.enabled IS DISTINCT FROM NEW.enabled
+    OR (OLD.locked_by IS NOT NULL AND NEW.locked_by IS NULL)
+)
+EXECUTE FUNCTION notify_tasks_changed();
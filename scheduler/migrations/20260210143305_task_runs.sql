@@ -0,0 +1,16 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS task_runs (
+    id UUID NOT NULL DEFAULT gen_random_uuid(),
+    task_id UUID NOT NULL,
+    attempt INT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    outcome SMALLINT NOT NULL,
+    error TEXT,
+
+    CONSTRAINT pk_task_runs PRIMARY KEY (id),
+    CONSTRAINT fk_task FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_runs_task_id ON task_runs (task_id, started_at);
@@ -0,0 +1,14 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS dead_letters (
+    id UUID NOT NULL DEFAULT gen_random_uuid(),
+    task_id UUID NOT NULL,
+    task JSONB NOT NULL, -- snapshot of the task at the time it failed
+    error TEXT NOT NULL,
+    attempts INT NOT NULL,
+    failed_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT pk_dead_letters PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_failed_at ON dead_letters (failed_at);
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN retry_policy JSONB;
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE task_runs
+ADD COLUMN output TEXT;
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN execution_timeout BIGINT; -- in milliseconds
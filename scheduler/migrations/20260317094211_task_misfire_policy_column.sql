@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN misfire_policy JSONB NOT NULL DEFAULT '"FireOnce"';
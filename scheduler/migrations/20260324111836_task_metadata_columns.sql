@@ -0,0 +1,11 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN title TEXT,
+ADD COLUMN description TEXT,
+ADD COLUMN created_by TEXT,
+ADD COLUMN assignee TEXT,
+ADD COLUMN chat_id BIGINT,
+ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+CREATE INDEX IF NOT EXISTS idx_tasks_chat_id ON tasks (chat_id, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_assignee ON tasks (assignee, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_created_by ON tasks (created_by, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_tags ON tasks USING GIN (tags);
+CREATE INDEX IF NOT EXISTS idx_tasks_next_run_id ON tasks (next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_created_at_id ON tasks (created_at, id);
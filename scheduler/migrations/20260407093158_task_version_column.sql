@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tasks
+ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
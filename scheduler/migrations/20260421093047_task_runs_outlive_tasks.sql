@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- The run history is kept after its task is deleted, also for runs recorded after that.
+ALTER TABLE task_runs
+DROP CONSTRAINT IF EXISTS fk_task;
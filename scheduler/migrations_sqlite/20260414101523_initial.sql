@@ -0,0 +1,71 @@
+-- Add migration script here
+
+-- Timestamps are unix milliseconds, JSON columns are TEXT.
+CREATE TABLE IF NOT EXISTS tasks (
+    id BLOB NOT NULL,
+    schedule_type INTEGER NOT NULL,
+    last_run INTEGER,
+    next_run INTEGER NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    retry_count INTEGER NOT NULL DEFAULT 0,
+    retry_delay INTEGER NOT NULL DEFAULT 1000, -- in milliseconds
+    max_retries INTEGER NOT NULL DEFAULT 3,
+    action TEXT NOT NULL,
+    start_date INTEGER,
+    end_date INTEGER,
+    cron_expression TEXT,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    delay_between_runs INTEGER,
+    retry_policy TEXT,
+    execution_timeout INTEGER,
+    misfire_policy TEXT NOT NULL DEFAULT '"FireOnce"',
+    title TEXT,
+    description TEXT,
+    created_by TEXT,
+    assignee TEXT,
+    chat_id INTEGER,
+    tags TEXT NOT NULL DEFAULT '[]',
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    version INTEGER NOT NULL DEFAULT 1,
+    locked_by TEXT,
+    locked_until INTEGER,
+
+    CONSTRAINT pk_tasks PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON tasks (next_run) WHERE enabled = 1;
+CREATE INDEX IF NOT EXISTS idx_tasks_chat_id ON tasks (chat_id, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_assignee ON tasks (assignee, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_created_by ON tasks (created_by, next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_next_run_id ON tasks (next_run, id);
+CREATE INDEX IF NOT EXISTS idx_tasks_created_at ON tasks (created_at, id);
+
+CREATE TABLE IF NOT EXISTS task_runs (
+    id BLOB NOT NULL,
+    task_id BLOB NOT NULL,
+    attempt INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    outcome INTEGER NOT NULL,
+    error TEXT,
+    output TEXT,
+
+    CONSTRAINT pk_task_runs PRIMARY KEY (id),
+    CONSTRAINT fk_task FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_runs_task_id ON task_runs (task_id, started_at);
+
+CREATE TABLE IF NOT EXISTS dead_letters (
+    id BLOB NOT NULL,
+    task_id BLOB NOT NULL,
+    task TEXT NOT NULL, -- snapshot of the task at the time it failed
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    failed_at INTEGER NOT NULL,
+
+    CONSTRAINT pk_dead_letters PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_failed_at ON dead_letters (failed_at);
@@ -0,0 +1,24 @@
+-- Add migration script here
+
+-- The run history is kept after its task is deleted, also for runs recorded after that.
+-- SQLite can't drop a constraint, so the table is rebuilt without it.
+CREATE TABLE task_runs_without_task_fk (
+    id BLOB NOT NULL,
+    task_id BLOB NOT NULL,
+    attempt INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    outcome INTEGER NOT NULL,
+    error TEXT,
+    output TEXT,
+
+    CONSTRAINT pk_task_runs PRIMARY KEY (id)
+);
+
+INSERT INTO task_runs_without_task_fk (id, task_id, attempt, started_at, finished_at, outcome, error, output)
+SELECT id, task_id, attempt, started_at, finished_at, outcome, error, output FROM task_runs;
+
+DROP TABLE task_runs;
+ALTER TABLE task_runs_without_task_fk RENAME TO task_runs;
+
+CREATE INDEX IF NOT EXISTS idx_task_runs_task_id ON task_runs (task_id, started_at);
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+
+/// The time source of the scheduler and the storages. Use a [`ManualClock`] in tests to
+/// control time instead of waiting for it to pass.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+    /// Resolves once [`Clock::now`] reached `deadline`.
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+}
+
+/// Reads the system time, the default clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        if let Ok(duration) = (deadline - Utc::now()).to_std() {
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
+/// A clock that stands still until it's advanced. Sleeping on it resolves once it was
+/// advanced to the deadline.
+pub struct ManualClock {
+    now: watch::Sender<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: watch::Sender::new(now),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now = after(*now, duration));
+    }
+
+    /// Moves the clock to `now`, also backwards.
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.now.send_replace(now);
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        // The sender lives as long as the clock, so waiting can't fail.
+        let _ = self.now.subscribe().wait_for(|now| *now >= deadline).await;
+    }
+}
+
+/// The time `duration` after `now`, saturating instead of overflowing.
+pub(crate) fn after(now: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| now.checked_add_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
@@ -1,9 +1,14 @@
-use crate::error::SchedulerError;
+use crate::{error::SchedulerError, storage::sqlite_storage};
 
 pub struct Migrator;
 
 impl Migrator {
+    /// Runs the Postgres migrations, or the SQLite ones for a `sqlite:` url.
     pub async fn run(database_url: &str) -> Result<(), SchedulerError> {
+        if database_url.starts_with("sqlite:") {
+            return Self::run_sqlite(database_url).await;
+        }
+
         let pool = sqlx::PgPool::connect(database_url)
             .await
             .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
@@ -15,4 +20,15 @@ impl Migrator {
 
         Ok(())
     }
+
+    async fn run_sqlite(database_url: &str) -> Result<(), SchedulerError> {
+        let pool = sqlite_storage::connect(database_url).await?;
+
+        sqlx::migrate!("./migrations_sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| SchedulerError::MigrationError(e.to_string()))?;
+
+        Ok(())
+    }
 }
@@ -1,5 +1,18 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
+/// How the scheduler should react to a failed task execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorDisposition {
+    /// Retry according to the task's retry policy.
+    Retryable,
+    /// Retrying won't help, so the task is dead-lettered right away.
+    Permanent,
+    /// Retry once the given time has passed, e.g. when rate limited.
+    RetryAfter(Duration),
+}
+
 #[derive(Debug, Error)]
 pub enum SchedulerError {
     #[error("Cron error: {0}")]
@@ -8,6 +21,9 @@ pub enum SchedulerError {
     #[error("No next occurrence found in cron schedule")]
     NoChronoNext,
 
+    #[error("Invalid timezone: {0}")]
+    InvalidTimezone(String),
+
     #[error("Scheduler is already running")]
     AlreadyRunning,
 
@@ -20,6 +36,18 @@ pub enum SchedulerError {
     #[error("Task execution error: {0}")]
     TaskExecutionError(String),
 
+    #[error("Permanent task execution error: {0}")]
+    PermanentExecutionError(String),
+
+    #[error("Rate limited, retry after {0:?}")]
+    RetryAfter(Duration),
+
+    #[error("Task execution timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Task executor panicked: {0}")]
+    ExecutorPanicked(String),
+
     #[error("Migration error: {0}")]
     MigrationError(String),
 
@@ -32,9 +60,53 @@ pub enum SchedulerError {
     #[error("Action not found in registry")]
     RegistryActionNotFound,
 
+    #[error("Dead letter {0} not found")]
+    DeadLetterNotFound(String),
+
+    #[error("Task {0} not found")]
+    TaskNotFound(String),
+
+    #[error("Invalid task state: {0}")]
+    InvalidTaskState(String),
+
+    #[error("Invalid schedule: {0}")]
+    InvalidSchedule(String),
+
+    #[error("Task {0} was changed since it was read")]
+    VersionConflict(String),
+
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 
     #[error("Serialization/Deserialization error: {0}")]
     SerdeError(#[from] serde_json::Error),
 }
+
+impl SchedulerError {
+    pub fn disposition(&self) -> ErrorDisposition {
+        match self {
+            SchedulerError::RetryAfter(delay) => ErrorDisposition::RetryAfter(*delay),
+            SchedulerError::DatabaseError(_)
+            | SchedulerError::TaskExecutionError(_)
+            | SchedulerError::Timeout(_)
+            | SchedulerError::ExecutorPanicked(_)
+            | SchedulerError::IoError(_) => ErrorDisposition::Retryable,
+            SchedulerError::CronError(_)
+            | SchedulerError::NoChronoNext
+            | SchedulerError::InvalidTimezone(_)
+            | SchedulerError::AlreadyRunning
+            | SchedulerError::NotRunning
+            | SchedulerError::PermanentExecutionError(_)
+            | SchedulerError::MigrationError(_)
+            | SchedulerError::UnsupportedAction
+            | SchedulerError::ActionMissing(_)
+            | SchedulerError::RegistryActionNotFound
+            | SchedulerError::DeadLetterNotFound(_)
+            | SchedulerError::TaskNotFound(_)
+            | SchedulerError::InvalidTaskState(_)
+            | SchedulerError::InvalidSchedule(_)
+            | SchedulerError::VersionConflict(_)
+            | SchedulerError::SerdeError(_) => ErrorDisposition::Permanent,
+        }
+    }
+}
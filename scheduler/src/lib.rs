@@ -1,3 +1,4 @@
+pub mod clock;
 pub mod db;
 pub mod error;
 pub mod storage;
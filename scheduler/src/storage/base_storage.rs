@@ -1,12 +1,85 @@
-use crate::{error::SchedulerError, task::default::Task};
+use crate::{
+    error::SchedulerError,
+    storage::task_query::{TaskPage, TaskQuery},
+    task::{
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        task_run::TaskRun,
+    },
+};
+use std::{sync::Arc, time::Duration};
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::{sync::Notify, task::JoinHandle};
 use uuid::Uuid;
 
 #[async_trait]
 pub trait Storage: Send + Sync {
+    /// Inserts a task that was never saved, or updates it if its version is still the
+    /// stored one, incrementing the version. Fails with [`SchedulerError::VersionConflict`]
+    /// otherwise, also when the task was deleted in the meantime.
     async fn save_task(&self, task: Task) -> Result<Uuid, SchedulerError>;
     async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, SchedulerError>;
     async fn get_all_tasks(&self) -> Result<Vec<Task>, SchedulerError>;
+    /// Deletes a task, keeping its recorded runs.
     async fn delete_task(&self, id: uuid::Uuid) -> Result<(), SchedulerError>;
+    /// The `set_task_*` methods only update the given fields and increment the version, so
+    /// that a scheduler saving the task concurrently merges the change instead of undoing it.
+    async fn set_task_enabled(&self, id: Uuid, enabled: bool) -> Result<(), SchedulerError>;
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: DateTime<Utc>,
+    ) -> Result<(), SchedulerError>;
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: DateTime<Utc>,
+    ) -> Result<(), SchedulerError>;
+    /// Returns the page of tasks matching `query`, see [`TaskQuery`].
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage, SchedulerError>;
     async fn get_ready_tasks(&self) -> Result<Vec<Task>, SchedulerError>;
+    /// Atomically leases up to `limit` ready tasks to `worker_id` for `lease`, the most
+    /// overdue first, skipping tasks that are still leased by another worker. Tasks whose
+    /// lease expired can be claimed again.
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, SchedulerError>;
+    /// Releases the lease that `worker_id` holds on a task.
+    async fn release_task(&self, id: Uuid, worker_id: &str) -> Result<(), SchedulerError>;
+    /// Extends the lease that `worker_id` holds on a task to `lease` from now. Does nothing
+    /// when the task is no longer leased to `worker_id`.
+    async fn renew_lease(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), SchedulerError>;
+    /// Returns the earliest time at which an enabled task can be claimed, taking leases
+    /// into account, or `None` when there is nothing to run.
+    async fn next_due_time(&self) -> Result<Option<DateTime<Utc>>, SchedulerError>;
+    /// Records an execution attempt of a task.
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), SchedulerError>;
+    /// Returns the recorded execution attempts of a task, oldest first.
+    async fn get_task_runs(&self, task_id: Uuid) -> Result<Vec<TaskRun>, SchedulerError>;
+    async fn save_dead_letter(&self, dead_letter: DeadLetter) -> Result<(), SchedulerError>;
+    async fn get_dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>, SchedulerError>;
+    /// Returns all dead letters, oldest first.
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, SchedulerError>;
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), SchedulerError>;
+    /// Wakes up the scheduler through `wakeup` whenever tasks are changed by another
+    /// process, returning the background task that watches for them so that the scheduler
+    /// can abort it once it stops. Storages that are not shared between processes don't
+    /// need to do anything.
+    async fn watch_changes(
+        &self,
+        _wakeup: Arc<Notify>,
+    ) -> Result<Option<JoinHandle<()>>, SchedulerError> {
+        Ok(None)
+    }
 }
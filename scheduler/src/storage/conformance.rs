@@ -0,0 +1,973 @@
+//! A conformance suite for [`Storage`] implementations, enabled by the `conformance`
+//! feature. Run it from a test of the storage:
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn test_my_storage_conformance() {
+//!     run_storage_conformance(|| async { Arc::new(MyStorage::new()) }).await;
+//! }
+//! ```
+//!
+//! Every check panics with a description of the behaviour the storage got wrong.
+//! Timestamps only have to round-trip with second precision.
+
+use std::{collections::HashSet, future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use uuid::Uuid;
+
+use crate::{
+    error::SchedulerError,
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskQuery, TaskSort},
+    },
+    task::{
+        action::TaskAction,
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        metadata::TaskMetadata,
+        misfire_policy::MisfirePolicy,
+        retry_policy::RetryPolicy,
+        task_run::{TaskRun, TaskRunOutcome},
+    },
+};
+
+/// Runs every check against storages created by `new_storage`, which has to return an
+/// empty storage on every call.
+pub async fn run_storage_conformance<S, F, Fut>(new_storage: F)
+where
+    S: Storage + ?Sized,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Arc<S>>,
+{
+    saved_task_round_trips(new_storage().await.as_ref()).await;
+    schedules_round_trip(new_storage().await.as_ref()).await;
+    edge_case_tasks_round_trip(new_storage().await.as_ref()).await;
+    missing_tasks_are_ignored(new_storage().await.as_ref()).await;
+    deleted_task_is_gone(new_storage().await.as_ref()).await;
+    save_task_checks_version(new_storage().await.as_ref()).await;
+    set_task_fields_bump_version(new_storage().await.as_ref()).await;
+    ready_tasks_are_enabled_and_due(new_storage().await.as_ref()).await;
+    claim_leases_most_overdue_tasks(new_storage().await.as_ref()).await;
+    renew_lease_extends_own_leases(new_storage().await.as_ref()).await;
+    next_due_time_skips_disabled_and_leased_tasks(new_storage().await.as_ref()).await;
+    next_due_time_looks_past_many_leased_tasks(new_storage().await.as_ref()).await;
+    query_tasks_pages_through_all_tasks(new_storage().await.as_ref()).await;
+    query_tasks_applies_filters(new_storage().await.as_ref()).await;
+    task_runs_are_ordered(new_storage().await.as_ref()).await;
+    dead_letters_are_ordered(new_storage().await.as_ref()).await;
+}
+
+/// The current time without the sub-second part, so that it round-trips through every storage.
+fn now() -> DateTime<Utc> {
+    DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap()
+}
+
+fn log_task(next_run: DateTime<Utc>) -> Task {
+    let now = now();
+    Task {
+        created_at: now,
+        updated_at: now,
+        ..Task::new_with_datetime(
+            next_run,
+            TaskAction::Log {
+                message: "conformance".to_string(),
+                level: "info".to_string(),
+            },
+        )
+    }
+}
+
+async fn save<S: Storage + ?Sized>(storage: &S, task: Task) -> Uuid {
+    storage
+        .save_task(task)
+        .await
+        .expect("save_task should insert a new task")
+}
+
+async fn get<S: Storage + ?Sized>(storage: &S, id: Uuid) -> Task {
+    storage
+        .get_task(id)
+        .await
+        .expect("get_task should succeed")
+        .expect("get_task should return a saved task")
+}
+
+fn ids(tasks: &[Task]) -> HashSet<Uuid> {
+    tasks.iter().map(|task| task.id).collect()
+}
+
+/// Compares everything the storage doesn't manage itself, i.e. all but `updated_at` and
+/// `version`.
+fn assert_same_task(expected: &Task, actual: &Task) {
+    assert_eq!(actual.id, expected.id, "the id should round-trip");
+    assert_eq!(
+        actual.next_run, expected.next_run,
+        "next_run should round-trip"
+    );
+    assert_eq!(
+        actual.last_run, expected.last_run,
+        "last_run should round-trip"
+    );
+    assert_eq!(
+        actual.enabled, expected.enabled,
+        "enabled should round-trip"
+    );
+    assert_eq!(
+        actual.retry_count, expected.retry_count,
+        "retry_count should round-trip"
+    );
+    assert_eq!(
+        actual.max_retries, expected.max_retries,
+        "max_retries should round-trip"
+    );
+    assert_eq!(
+        actual.retry_delay, expected.retry_delay,
+        "retry_delay should round-trip"
+    );
+    assert_eq!(
+        actual.schedule, expected.schedule,
+        "the schedule should round-trip"
+    );
+    assert_eq!(
+        serde_json::to_value(&actual.action).unwrap(),
+        serde_json::to_value(&expected.action).unwrap(),
+        "the action should round-trip"
+    );
+    assert_eq!(
+        actual.delay_between_runs, expected.delay_between_runs,
+        "delay_between_runs should round-trip"
+    );
+    assert_eq!(
+        actual.timezone, expected.timezone,
+        "the timezone should round-trip"
+    );
+    assert_eq!(
+        actual.retry_policy, expected.retry_policy,
+        "the retry policy should round-trip"
+    );
+    assert_eq!(
+        actual.timeout, expected.timeout,
+        "the timeout should round-trip"
+    );
+    assert_eq!(
+        actual.misfire_policy, expected.misfire_policy,
+        "the misfire policy should round-trip"
+    );
+    assert_eq!(
+        actual.metadata, expected.metadata,
+        "the metadata should round-trip"
+    );
+    assert_eq!(
+        actual.created_at, expected.created_at,
+        "created_at should round-trip"
+    );
+}
+
+async fn saved_task_round_trips<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let task = Task {
+        last_run: Some(now - chrono::Duration::days(1)),
+        retry_count: 2,
+        created_at: now - chrono::Duration::hours(1),
+        ..log_task(now + chrono::Duration::hours(1))
+    }
+    .with_max_retries(5)
+    .with_retry_delay(Duration::from_millis(1500))
+    .with_retry_policy(RetryPolicy::linear().with_max_delay(Duration::from_secs(600)))
+    .with_timeout(Duration::from_secs(30))
+    .with_misfire_policy(MisfirePolicy::DropOlderThan(Duration::from_secs(600)))
+    .with_delay_between_runs(chrono::Duration::minutes(90))
+    .with_timezone(Tz::Europe__Sarajevo)
+    .with_metadata(TaskMetadata {
+        title: Some("Standup".to_string()),
+        description: Some("Daily standup".to_string()),
+        created_by: Some("42".to_string()),
+        assignee: Some("@kapo".to_string()),
+        chat_id: Some(-100123),
+        tags: vec!["work".to_string(), "daily".to_string()],
+    });
+
+    let id = save(storage, task.clone()).await;
+    assert_eq!(id, task.id, "save_task should return the id of the task");
+
+    let saved = get(storage, id).await;
+    assert_same_task(&task, &saved);
+    assert_eq!(
+        saved.version, 1,
+        "inserting a task should set its version to 1"
+    );
+
+    let all = storage
+        .get_all_tasks()
+        .await
+        .expect("get_all_tasks should succeed");
+    assert_eq!(
+        ids(&all),
+        HashSet::from([id]),
+        "get_all_tasks should return the saved task"
+    );
+}
+
+async fn schedules_round_trip<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let range = Task::new_with_datetime_range(
+        now,
+        now + chrono::Duration::days(30),
+        TaskAction::Log {
+            message: "range".to_string(),
+            level: "info".to_string(),
+        },
+    );
+    let range = Task {
+        created_at: now,
+        ..range
+    };
+    let cron = Task::new_with_cron(
+        "0 30 8 * * Mon-Fri *",
+        Tz::Europe__Sarajevo,
+        TaskAction::Log {
+            message: "cron".to_string(),
+            level: "info".to_string(),
+        },
+    )
+    .expect("the cron expression should parse");
+    let cron = Task {
+        created_at: now,
+        ..cron
+    };
+
+    for task in [range, cron] {
+        let id = save(storage, task.clone()).await;
+        assert_same_task(&task, &get(storage, id).await);
+    }
+}
+
+async fn edge_case_tasks_round_trip<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let tasks = [
+        Task {
+            enabled: false,
+            ..log_task(now - chrono::Duration::days(365))
+        }
+        .with_max_retries(0)
+        .with_retry_delay(Duration::ZERO),
+        log_task(now).with_metadata(TaskMetadata {
+            title: Some("Sastanak 📅 ćčžšđ 'quoted' \"double\"".to_string()),
+            description: Some(String::new()),
+            tags: vec!["š".to_string(), "with space".to_string()],
+            ..Default::default()
+        }),
+        Task {
+            action: Some(TaskAction::Log {
+                message: "\n\t{}[]\\".to_string(),
+                level: String::new(),
+            }),
+            ..log_task(now + chrono::Duration::days(365 * 10))
+        }
+        .with_misfire_policy(MisfirePolicy::FireAll),
+    ];
+
+    for task in tasks {
+        let id = save(storage, task.clone()).await;
+        assert_same_task(&task, &get(storage, id).await);
+    }
+}
+
+async fn missing_tasks_are_ignored<S: Storage + ?Sized>(storage: &S) {
+    let id = Uuid::new_v4();
+
+    assert!(
+        storage
+            .get_task(id)
+            .await
+            .expect("get_task should succeed")
+            .is_none(),
+        "get_task should return None for a missing task"
+    );
+    storage
+        .delete_task(id)
+        .await
+        .expect("deleting a missing task should succeed");
+    storage
+        .set_task_enabled(id, false)
+        .await
+        .expect("set_task_enabled should ignore a missing task");
+    storage
+        .set_task_next_run(id, now())
+        .await
+        .expect("set_task_next_run should ignore a missing task");
+    assert!(
+        storage
+            .get_task(id)
+            .await
+            .expect("get_task should succeed")
+            .is_none(),
+        "updating a missing task should not create it"
+    );
+    assert!(
+        storage
+            .get_task_runs(id)
+            .await
+            .expect("get_task_runs should succeed")
+            .is_empty(),
+        "a missing task should have no runs"
+    );
+    assert!(
+        storage
+            .get_dead_letter(id)
+            .await
+            .expect("get_dead_letter should succeed")
+            .is_none(),
+        "get_dead_letter should return None for a missing dead letter"
+    );
+}
+
+async fn deleted_task_is_gone<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let deleted = save(storage, log_task(now - chrono::Duration::minutes(1))).await;
+    let kept = save(storage, log_task(now - chrono::Duration::minutes(1))).await;
+    storage
+        .record_task_run(run(deleted, 1, now, TaskRunOutcome::Succeeded))
+        .await
+        .expect("record_task_run should succeed");
+
+    storage
+        .delete_task(deleted)
+        .await
+        .expect("delete_task should succeed");
+
+    assert!(
+        storage
+            .get_task(deleted)
+            .await
+            .expect("get_task should succeed")
+            .is_none(),
+        "a deleted task should not be returned by get_task"
+    );
+    let all = storage
+        .get_all_tasks()
+        .await
+        .expect("get_all_tasks should succeed");
+    assert_eq!(
+        ids(&all),
+        HashSet::from([kept]),
+        "delete_task should delete only its task"
+    );
+    let ready = storage
+        .get_ready_tasks()
+        .await
+        .expect("get_ready_tasks should succeed");
+    assert_eq!(
+        ids(&ready),
+        HashSet::from([kept]),
+        "a deleted task should not be ready"
+    );
+
+    // Also a run that finished after its task was deleted.
+    storage
+        .record_task_run(run(
+            deleted,
+            2,
+            now + chrono::Duration::minutes(1),
+            TaskRunOutcome::Failed,
+        ))
+        .await
+        .expect("record_task_run should succeed for a deleted task");
+    assert_eq!(
+        storage
+            .get_task_runs(deleted)
+            .await
+            .expect("get_task_runs should succeed")
+            .len(),
+        2,
+        "deleting a task should keep its runs"
+    );
+}
+
+async fn save_task_checks_version<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let task = log_task(now + chrono::Duration::hours(1));
+    let id = save(storage, task.clone()).await;
+
+    assert!(
+        matches!(
+            storage.save_task(task.clone()).await,
+            Err(SchedulerError::VersionConflict(_))
+        ),
+        "inserting a task twice should be a version conflict"
+    );
+
+    let stale = get(storage, id).await;
+    let mut updated = stale.clone();
+    updated.retry_count = 1;
+    updated.created_at = now - chrono::Duration::days(7);
+    updated.updated_at = now - chrono::Duration::days(7);
+    storage
+        .save_task(updated)
+        .await
+        .expect("saving a task with the stored version should succeed");
+
+    let saved = get(storage, id).await;
+    assert_eq!(
+        saved.version, 2,
+        "saving a task should increment its version"
+    );
+    assert_eq!(saved.retry_count, 1, "saving a task should update it");
+    assert_eq!(
+        saved.created_at, task.created_at,
+        "saving a task should keep its created_at"
+    );
+    assert!(
+        saved.updated_at >= now,
+        "saving a task should set its updated_at to the current time"
+    );
+
+    assert!(
+        matches!(
+            storage.save_task(stale).await,
+            Err(SchedulerError::VersionConflict(_))
+        ),
+        "saving a stale task should be a version conflict"
+    );
+    assert_eq!(
+        get(storage, id).await.retry_count,
+        1,
+        "a conflicting save should not change the task"
+    );
+
+    storage
+        .delete_task(id)
+        .await
+        .expect("delete_task should succeed");
+    assert!(
+        matches!(
+            storage.save_task(saved).await,
+            Err(SchedulerError::VersionConflict(_))
+        ),
+        "saving a deleted task should be a version conflict"
+    );
+    assert!(
+        storage
+            .get_task(id)
+            .await
+            .expect("get_task should succeed")
+            .is_none(),
+        "a conflicting save should not restore a deleted task"
+    );
+}
+
+async fn set_task_fields_bump_version<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let task = log_task(now + chrono::Duration::hours(1)).with_max_retries(7);
+    let id = save(storage, task.clone()).await;
+
+    storage
+        .set_task_enabled(id, false)
+        .await
+        .expect("set_task_enabled should succeed");
+    let saved = get(storage, id).await;
+    assert!(!saved.enabled, "set_task_enabled should update enabled");
+    assert_eq!(
+        saved.version, 2,
+        "set_task_enabled should increment the version"
+    );
+    assert_eq!(
+        saved.max_retries, 7,
+        "set_task_enabled should keep the other fields"
+    );
+
+    let next_run = now + chrono::Duration::hours(2);
+    storage
+        .set_task_next_run(id, next_run)
+        .await
+        .expect("set_task_next_run should succeed");
+    let saved = get(storage, id).await;
+    assert_eq!(
+        saved.next_run, next_run,
+        "set_task_next_run should update next_run"
+    );
+    assert_eq!(
+        saved.version, 3,
+        "set_task_next_run should increment the version"
+    );
+    assert!(
+        !saved.enabled,
+        "set_task_next_run should keep the other fields"
+    );
+
+    let schedule = TaskType::Range {
+        start_date: now + chrono::Duration::days(1),
+        end_date: now + chrono::Duration::days(2),
+    };
+    storage
+        .set_task_schedule(id, schedule.clone(), now + chrono::Duration::days(1))
+        .await
+        .expect("set_task_schedule should succeed");
+    let saved = get(storage, id).await;
+    assert_eq!(
+        saved.schedule, schedule,
+        "set_task_schedule should update the schedule"
+    );
+    assert_eq!(
+        saved.next_run,
+        now + chrono::Duration::days(1),
+        "set_task_schedule should update next_run"
+    );
+    assert_eq!(
+        saved.version, 4,
+        "set_task_schedule should increment the version"
+    );
+}
+
+async fn ready_tasks_are_enabled_and_due<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let due = save(storage, log_task(now - chrono::Duration::minutes(1))).await;
+    save(
+        storage,
+        Task {
+            enabled: false,
+            ..log_task(now - chrono::Duration::minutes(1))
+        },
+    )
+    .await;
+    save(storage, log_task(now + chrono::Duration::hours(1))).await;
+
+    let ready = storage
+        .get_ready_tasks()
+        .await
+        .expect("get_ready_tasks should succeed");
+    assert_eq!(
+        ids(&ready),
+        HashSet::from([due]),
+        "get_ready_tasks should return only the enabled tasks that are due"
+    );
+}
+
+async fn claim_leases_most_overdue_tasks<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let oldest = save(storage, log_task(now - chrono::Duration::hours(3))).await;
+    let older = save(storage, log_task(now - chrono::Duration::hours(2))).await;
+    let old = save(storage, log_task(now - chrono::Duration::hours(1))).await;
+    save(storage, log_task(now + chrono::Duration::hours(1))).await;
+    let lease = Duration::from_secs(60 * 60);
+
+    let claimed = storage
+        .claim_ready_tasks("first", lease, 2)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(
+        ids(&claimed),
+        HashSet::from([oldest, older]),
+        "claim_ready_tasks should claim the most overdue tasks up to the limit"
+    );
+
+    let claimed = storage
+        .claim_ready_tasks("second", lease, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(
+        ids(&claimed),
+        HashSet::from([old]),
+        "claim_ready_tasks should skip tasks leased by another worker"
+    );
+
+    storage
+        .release_task(oldest, "second")
+        .await
+        .expect("release_task should succeed");
+    let claimed = storage
+        .claim_ready_tasks("second", lease, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert!(
+        claimed.is_empty(),
+        "release_task should only release leases of the given worker"
+    );
+
+    storage
+        .release_task(oldest, "first")
+        .await
+        .expect("release_task should succeed");
+    let claimed = storage
+        .claim_ready_tasks("second", lease, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(
+        ids(&claimed),
+        HashSet::from([oldest]),
+        "a released task should be claimable again"
+    );
+}
+
+async fn renew_lease_extends_own_leases<S: Storage + ?Sized>(storage: &S) {
+    let task = save(storage, log_task(now() - chrono::Duration::hours(1))).await;
+    let lease = Duration::from_secs(60 * 60);
+
+    let claimed = storage
+        .claim_ready_tasks("first", Duration::ZERO, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(ids(&claimed), HashSet::from([task]));
+
+    storage
+        .renew_lease(task, "second", lease)
+        .await
+        .expect("renew_lease should succeed");
+    let claimed = storage
+        .claim_ready_tasks("second", Duration::ZERO, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(
+        ids(&claimed),
+        HashSet::from([task]),
+        "renew_lease should only renew leases of the given worker"
+    );
+
+    storage
+        .renew_lease(task, "second", lease)
+        .await
+        .expect("renew_lease should succeed");
+    let claimed = storage
+        .claim_ready_tasks("first", lease, 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert!(
+        claimed.is_empty(),
+        "renew_lease should keep the task leased for the new duration"
+    );
+}
+
+async fn next_due_time_skips_disabled_and_leased_tasks<S: Storage + ?Sized>(storage: &S) {
+    assert_eq!(
+        storage
+            .next_due_time()
+            .await
+            .expect("next_due_time should succeed"),
+        None,
+        "next_due_time should be None without tasks"
+    );
+
+    let now = now();
+    save(
+        storage,
+        Task {
+            enabled: false,
+            ..log_task(now - chrono::Duration::hours(2))
+        },
+    )
+    .await;
+    let leased = save(storage, log_task(now - chrono::Duration::hours(1))).await;
+    let claimed = storage
+        .claim_ready_tasks("worker", Duration::from_secs(2 * 60 * 60), 10)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(ids(&claimed), HashSet::from([leased]));
+
+    let next_run = now + chrono::Duration::hours(1);
+    save(storage, log_task(next_run)).await;
+
+    assert_eq!(
+        storage
+            .next_due_time()
+            .await
+            .expect("next_due_time should succeed"),
+        Some(next_run),
+        "next_due_time should skip disabled tasks and tasks leased past it"
+    );
+}
+
+async fn next_due_time_looks_past_many_leased_tasks<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    for minutes in 1..=150 {
+        save(storage, log_task(now - chrono::Duration::minutes(minutes))).await;
+    }
+    let claimed = storage
+        .claim_ready_tasks("worker", Duration::from_secs(2 * 60 * 60), 150)
+        .await
+        .expect("claim_ready_tasks should succeed");
+    assert_eq!(claimed.len(), 150);
+
+    let next_run = now + chrono::Duration::hours(1);
+    save(storage, log_task(next_run)).await;
+
+    assert_eq!(
+        storage
+            .next_due_time()
+            .await
+            .expect("next_due_time should succeed"),
+        Some(next_run),
+        "next_due_time should find the first unleased task behind any number of leased ones"
+    );
+}
+
+async fn query_pages<S: Storage + ?Sized>(
+    storage: &S,
+    query: TaskQuery,
+    limit: usize,
+) -> Vec<(DateTime<Utc>, Uuid)> {
+    let mut query = query.with_limit(limit);
+    let mut queried = Vec::new();
+    loop {
+        let page = storage
+            .query_tasks(&query)
+            .await
+            .expect("query_tasks should succeed");
+        assert!(
+            page.tasks.len() <= limit,
+            "query_tasks should respect the limit"
+        );
+        queried.extend(page.tasks.iter().map(|task| (task.next_run, task.id)));
+        match page.next_cursor {
+            Some(cursor) => query = query.after(cursor),
+            None => return queried,
+        }
+    }
+}
+
+async fn query_tasks_pages_through_all_tasks<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let mut expected = Vec::new();
+    for hours in [3, 1, 2, 2, 5] {
+        let task = log_task(now + chrono::Duration::hours(hours)).with_metadata(TaskMetadata {
+            chat_id: Some(1),
+            ..Default::default()
+        });
+        expected.push((task.next_run, save(storage, task).await));
+    }
+    save(storage, log_task(now)).await;
+    expected.sort();
+
+    assert_eq!(
+        query_pages(storage, TaskQuery::new().with_chat_id(1), 2).await,
+        expected,
+        "paging through query_tasks should return every matching task once, ordered by next_run and id"
+    );
+
+    expected.reverse();
+    assert_eq!(
+        query_pages(
+            storage,
+            TaskQuery::new()
+                .with_chat_id(1)
+                .with_sort(TaskSort::NextRunDesc),
+            2
+        )
+        .await,
+        expected,
+        "paging through query_tasks in descending order should return every matching task once"
+    );
+}
+
+async fn query_tasks_applies_filters<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let task = |hours: i64, chat_id: i64, assignee: &str, tags: &[&str]| {
+        log_task(now + chrono::Duration::hours(hours)).with_metadata(TaskMetadata {
+            created_by: Some(format!("creator-{}", chat_id)),
+            assignee: Some(assignee.to_string()),
+            chat_id: Some(chat_id),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        })
+    };
+    let ana_home = save(storage, task(3, 1, "@ana", &["home"])).await;
+    let ana_weekly = save(storage, task(1, 1, "@ana", &["home", "weekly"])).await;
+    let ivo = save(storage, task(2, 1, "@ivo", &["work", "weekly"])).await;
+    let disabled = save(
+        storage,
+        Task {
+            enabled: false,
+            ..task(4, 1, "@ana", &[])
+        },
+    )
+    .await;
+    let other_chat = save(storage, task(1, 2, "@ana", &["home", "weekly"])).await;
+
+    let query = |query: TaskQuery| async move {
+        storage
+            .query_tasks(&query)
+            .await
+            .expect("query_tasks should succeed")
+            .tasks
+            .iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        query(TaskQuery::new().with_chat_id(1).with_assignee("@ana")).await,
+        vec![ana_weekly, ana_home, disabled],
+        "query_tasks should filter by chat and assignee"
+    );
+    assert_eq!(
+        query(TaskQuery::new().with_created_by("creator-2")).await,
+        vec![other_chat],
+        "query_tasks should filter by creator"
+    );
+    assert_eq!(
+        query(TaskQuery::new().with_enabled(false)).await,
+        vec![disabled],
+        "query_tasks should filter by enabled"
+    );
+    assert_eq!(
+        HashSet::<Uuid>::from_iter(
+            query(TaskQuery::new().with_tag("home").with_tag("weekly")).await
+        ),
+        HashSet::from([ana_weekly, other_chat]),
+        "query_tasks should only return tasks with every given tag"
+    );
+    assert_eq!(
+        query(TaskQuery::new().with_next_run_between(
+            now + chrono::Duration::hours(2),
+            now + chrono::Duration::hours(4)
+        ))
+        .await,
+        vec![ivo, ana_home],
+        "query_tasks should filter by next_run, including the start and excluding the end"
+    );
+}
+
+fn run(task_id: Uuid, attempt: u32, started_at: DateTime<Utc>, outcome: TaskRunOutcome) -> TaskRun {
+    TaskRun {
+        id: Uuid::new_v4(),
+        task_id,
+        attempt,
+        started_at,
+        finished_at: started_at + chrono::Duration::seconds(1),
+        outcome,
+        error: None,
+        output: None,
+    }
+}
+
+async fn task_runs_are_ordered<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let id = save(storage, log_task(now)).await;
+    let other = save(storage, log_task(now)).await;
+
+    let later = TaskRun {
+        output: Some("done ✅".to_string()),
+        ..run(
+            id,
+            1,
+            now + chrono::Duration::seconds(2),
+            TaskRunOutcome::Succeeded,
+        )
+    };
+    let retry = TaskRun {
+        error: Some("Action failed".to_string()),
+        ..run(id, 2, now, TaskRunOutcome::TimedOut)
+    };
+    let first = TaskRun {
+        error: Some("Action failed".to_string()),
+        ..run(id, 1, now, TaskRunOutcome::Failed)
+    };
+    for task_run in [
+        later.clone(),
+        retry.clone(),
+        first.clone(),
+        run(other, 1, now, TaskRunOutcome::Skipped),
+    ] {
+        storage
+            .record_task_run(task_run)
+            .await
+            .expect("record_task_run should succeed");
+    }
+
+    let runs = storage
+        .get_task_runs(id)
+        .await
+        .expect("get_task_runs should succeed");
+    let expected = [first, retry, later];
+    assert_eq!(
+        runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+        expected.iter().map(|run| run.id).collect::<Vec<_>>(),
+        "get_task_runs should return only the task's runs, ordered by started_at and attempt"
+    );
+    for (run, expected) in runs.iter().zip(&expected) {
+        assert_eq!(
+            run.task_id, expected.task_id,
+            "the task id should round-trip"
+        );
+        assert_eq!(
+            run.attempt, expected.attempt,
+            "the attempt should round-trip"
+        );
+        assert_eq!(
+            run.started_at, expected.started_at,
+            "started_at should round-trip"
+        );
+        assert_eq!(
+            run.finished_at, expected.finished_at,
+            "finished_at should round-trip"
+        );
+        assert_eq!(
+            run.outcome, expected.outcome,
+            "the outcome should round-trip"
+        );
+        assert_eq!(run.error, expected.error, "the error should round-trip");
+        assert_eq!(run.output, expected.output, "the output should round-trip");
+    }
+}
+
+async fn dead_letters_are_ordered<S: Storage + ?Sized>(storage: &S) {
+    let now = now();
+    let task = log_task(now).with_max_retries(2);
+    let dead_letter = |failed_at: DateTime<Utc>| DeadLetter {
+        id: Uuid::new_v4(),
+        task: task.clone(),
+        error: "Action failed".to_string(),
+        attempts: 3,
+        failed_at,
+    };
+    let later = dead_letter(now + chrono::Duration::seconds(1));
+    let earlier = dead_letter(now);
+    for dead_letter in [later.clone(), earlier.clone()] {
+        storage
+            .save_dead_letter(dead_letter)
+            .await
+            .expect("save_dead_letter should succeed");
+    }
+
+    let dead_letters = storage
+        .get_dead_letters()
+        .await
+        .expect("get_dead_letters should succeed");
+    assert_eq!(
+        dead_letters.iter().map(|d| d.id).collect::<Vec<_>>(),
+        vec![earlier.id, later.id],
+        "get_dead_letters should order the dead letters by failed_at"
+    );
+
+    let saved = storage
+        .get_dead_letter(earlier.id)
+        .await
+        .expect("get_dead_letter should succeed")
+        .expect("get_dead_letter should return a saved dead letter");
+    assert_same_task(&task, &saved.task);
+    assert_eq!(saved.error, earlier.error, "the error should round-trip");
+    assert_eq!(
+        saved.attempts, earlier.attempts,
+        "the attempts should round-trip"
+    );
+    assert_eq!(
+        saved.failed_at, earlier.failed_at,
+        "failed_at should round-trip"
+    );
+
+    storage
+        .delete_dead_letter(earlier.id)
+        .await
+        .expect("delete_dead_letter should succeed");
+    let dead_letters = storage
+        .get_dead_letters()
+        .await
+        .expect("get_dead_letters should succeed");
+    assert_eq!(
+        dead_letters.iter().map(|d| d.id).collect::<Vec<_>>(),
+        vec![later.id],
+        "delete_dead_letter should delete only its dead letter"
+    );
+}
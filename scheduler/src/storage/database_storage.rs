@@ -1,14 +1,36 @@
+use std::{sync::Arc, time::Duration};
+
 use async_trait::async_trait;
+use sqlx::{Postgres, QueryBuilder, postgres::PgListener};
+use tokio::{sync::Notify, task::JoinHandle};
 use uuid::Uuid;
 
 use crate::{
+    clock::{Clock, SystemClock},
     error::SchedulerError,
-    storage::base_storage::Storage,
-    task::default::{Task, TaskDb},
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskPage, TaskQuery, TaskSort},
+    },
+    task::{
+        dead_letter::{DeadLetter, DeadLetterDb},
+        default::{
+            Task, TaskDb, TaskType, from_offset_datetime, to_offset_datetime,
+            to_precise_offset_datetime,
+        },
+        task_run::{TaskRun, TaskRunDb},
+    },
 };
 
+static TASKS_CHANGED_CHANNEL: &str = "tasks_changed";
+
+// The query macros need the column list inline, this is the same list for the queries that
+// are built at runtime.
+static TASK_COLUMNS: &str = "id, schedule_type, last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version";
+
 pub struct DatabaseStorage {
     pub pool: sqlx::PgPool,
+    clock: Arc<dyn Clock>,
 }
 
 impl DatabaseStorage {
@@ -16,7 +38,21 @@ impl DatabaseStorage {
         let pool = sqlx::PgPool::connect(database_url)
             .await
             .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
-        Ok(DatabaseStorage { pool })
+        Ok(DatabaseStorage {
+            pool,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// The clock deciding which tasks are due, its time is passed to the queries. Defaults
+    /// to the system time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn now(&self) -> Result<sqlx::types::time::OffsetDateTime, SchedulerError> {
+        to_precise_offset_datetime(self.clock.now())
     }
 }
 
@@ -24,44 +60,111 @@ impl DatabaseStorage {
 impl Storage for DatabaseStorage {
     async fn save_task(&self, task: Task) -> Result<Uuid, crate::error::SchedulerError> {
         let db_task = Task::to_db_task(&task)?;
+        let now = self.now()?;
 
-        let task_id = sqlx::query_scalar!(
-            "INSERT INTO tasks (id, schedule_type, last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-            ON CONFLICT (id) DO UPDATE SET
-                schedule_type = EXCLUDED.schedule_type,
-                last_run = EXCLUDED.last_run,
-                next_run = EXCLUDED.next_run,
-                retry_count = EXCLUDED.retry_count,
-                max_retries = EXCLUDED.max_retries,
-                retry_delay = EXCLUDED.retry_delay,
-                enabled = EXCLUDED.enabled,
-                action = EXCLUDED.action,
-                start_date = EXCLUDED.start_date,
-                end_date = EXCLUDED.end_date
-            RETURNING id",
-            db_task.id,
-            db_task.schedule_type,
-            db_task.last_run,
-            db_task.next_run,
-            db_task.retry_count,
-            db_task.max_retries,
-            db_task.retry_delay,
-            db_task.enabled,
-            db_task.action,
-            db_task.start_date,
-            db_task.end_date
-        ).fetch_one(&self.pool)
+        // Tasks that were never saved are inserted, the others are only updated if nobody
+        // else saved them since they were read.
+        let task_id = if db_task.version == 0 {
+            sqlx::query_scalar!(
+                "INSERT INTO tasks (id, schedule_type, last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, 1)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id",
+                db_task.id,
+                db_task.schedule_type,
+                db_task.last_run,
+                db_task.next_run,
+                db_task.retry_count,
+                db_task.max_retries,
+                db_task.retry_delay,
+                db_task.enabled,
+                db_task.action,
+                db_task.start_date,
+                db_task.end_date,
+                db_task.cron_expression,
+                db_task.timezone,
+                db_task.delay_between_runs,
+                db_task.retry_policy,
+                db_task.execution_timeout,
+                db_task.misfire_policy,
+                db_task.title,
+                db_task.description,
+                db_task.created_by,
+                db_task.assignee,
+                db_task.chat_id,
+                &db_task.tags,
+                db_task.created_at,
+                now
+            ).fetch_optional(&self.pool)
+                .await
+                .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?
+        } else {
+            sqlx::query_scalar!(
+                "UPDATE tasks SET
+                    schedule_type = $2,
+                    last_run = $3,
+                    next_run = $4,
+                    retry_count = $5,
+                    max_retries = $6,
+                    retry_delay = $7,
+                    enabled = $8,
+                    action = $9,
+                    start_date = $10,
+                    end_date = $11,
+                    cron_expression = $12,
+                    timezone = $13,
+                    delay_between_runs = $14,
+                    retry_policy = $15,
+                    execution_timeout = $16,
+                    misfire_policy = $17,
+                    title = $18,
+                    description = $19,
+                    created_by = $20,
+                    assignee = $21,
+                    chat_id = $22,
+                    tags = $23,
+                    updated_at = $25,
+                    version = version + 1
+                WHERE id = $1 AND version = $24
+                RETURNING id",
+                db_task.id,
+                db_task.schedule_type,
+                db_task.last_run,
+                db_task.next_run,
+                db_task.retry_count,
+                db_task.max_retries,
+                db_task.retry_delay,
+                db_task.enabled,
+                db_task.action,
+                db_task.start_date,
+                db_task.end_date,
+                db_task.cron_expression,
+                db_task.timezone,
+                db_task.delay_between_runs,
+                db_task.retry_policy,
+                db_task.execution_timeout,
+                db_task.misfire_policy,
+                db_task.title,
+                db_task.description,
+                db_task.created_by,
+                db_task.assignee,
+                db_task.chat_id,
+                &db_task.tags,
+                db_task.version,
+                now
+            )
+            .fetch_optional(&self.pool)
             .await
-            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?
+        };
 
-        Ok(task_id)
+        task_id.ok_or_else(|| SchedulerError::VersionConflict(task.id.to_string()))
     }
 
     async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, crate::error::SchedulerError> {
         let record = sqlx::query_as!(
             TaskDb,
-            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date
+            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version
             FROM tasks WHERE id = $1",
             id
         ).fetch_optional(&self.pool)
@@ -74,7 +177,7 @@ impl Storage for DatabaseStorage {
     async fn get_all_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
         let records = sqlx::query_as!(
             TaskDb,
-            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date
+            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version
             FROM tasks"
         ).fetch_all(&self.pool)
             .await
@@ -93,11 +196,140 @@ impl Storage for DatabaseStorage {
         Ok(())
     }
 
+    async fn set_task_enabled(
+        &self,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query!(
+            "UPDATE tasks SET enabled = $2, updated_at = $3, version = version + 1 WHERE id = $1",
+            id,
+            enabled,
+            self.now()?
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query!(
+            "UPDATE tasks SET next_run = $2, updated_at = $3, version = version + 1 WHERE id = $1",
+            id,
+            to_offset_datetime(next_run)?,
+            self.now()?
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let (schedule_type, start_date, end_date, cron_expression) = schedule.to_db_columns()?;
+
+        sqlx::query!(
+            "UPDATE tasks SET schedule_type = $2, start_date = $3, end_date = $4, cron_expression = $5, next_run = $6, updated_at = $7, version = version + 1
+            WHERE id = $1",
+            id,
+            schedule_type,
+            start_date,
+            end_date,
+            cron_expression,
+            to_offset_datetime(next_run)?,
+            self.now()?
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn query_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<TaskPage, crate::error::SchedulerError> {
+        let mut builder =
+            QueryBuilder::<Postgres>::new(format!("SELECT {} FROM tasks WHERE TRUE", TASK_COLUMNS));
+
+        if let Some(chat_id) = query.chat_id {
+            builder.push(" AND chat_id = ").push_bind(chat_id);
+        }
+        if let Some(assignee) = &query.assignee {
+            builder.push(" AND assignee = ").push_bind(assignee.clone());
+        }
+        if let Some(created_by) = &query.created_by {
+            builder
+                .push(" AND created_by = ")
+                .push_bind(created_by.clone());
+        }
+        if let Some(enabled) = query.enabled {
+            builder.push(" AND enabled = ").push_bind(enabled);
+        }
+        if let Some(from) = query.next_run_from {
+            builder
+                .push(" AND next_run >= ")
+                .push_bind(to_offset_datetime(from)?);
+        }
+        if let Some(until) = query.next_run_until {
+            builder
+                .push(" AND next_run < ")
+                .push_bind(to_offset_datetime(until)?);
+        }
+        if !query.tags.is_empty() {
+            builder.push(" AND tags @> ").push_bind(query.tags.clone());
+        }
+
+        let (column, direction, comparison) = match query.sort {
+            TaskSort::NextRunAsc => ("next_run", "ASC", ">"),
+            TaskSort::NextRunDesc => ("next_run", "DESC", "<"),
+            TaskSort::CreatedAtAsc => ("created_at", "ASC", ">"),
+            TaskSort::CreatedAtDesc => ("created_at", "DESC", "<"),
+        };
+
+        if let Some(cursor) = &query.cursor {
+            builder
+                .push(format!(" AND ({}, id) {} (", column, comparison))
+                .push_bind(to_offset_datetime(cursor.key())?)
+                .push(", ")
+                .push_bind(cursor.id())
+                .push(")");
+        }
+
+        builder
+            .push(format!(
+                " ORDER BY {} {}, id {} LIMIT ",
+                column, direction, direction
+            ))
+            .push_bind(i64::try_from(query.limit.saturating_add(1)).unwrap_or(i64::MAX));
+
+        let records = builder
+            .build_query_as::<TaskDb>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        let tasks: Result<Vec<Task>, SchedulerError> =
+            records.into_iter().map(Task::from_db_task).collect();
+        Ok(query.page_from(tasks?))
+    }
+
     async fn get_ready_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
         let records = sqlx::query_as!(
             TaskDb,
-            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date
-            FROM tasks WHERE next_run <= NOW() AND enabled = TRUE",
+            "SELECT id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version
+            FROM tasks WHERE next_run <= $1 AND enabled = TRUE",
+            self.now()?
         ).fetch_all(&self.pool)
             .await
             .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
@@ -106,4 +338,220 @@ impl Storage for DatabaseStorage {
             records.into_iter().map(Task::from_db_task).collect();
         Ok(tasks?)
     }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let records = sqlx::query_as!(
+            TaskDb,
+            "UPDATE tasks SET locked_by = $1, locked_until = $4 + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM tasks
+                WHERE next_run <= $4 AND enabled = TRUE
+                    AND (locked_until IS NULL OR locked_until <= $4)
+                ORDER BY next_run
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, schedule_type as \"schedule_type: i16\", last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version",
+            worker_id,
+            lease.as_secs_f64(),
+            i64::try_from(limit).unwrap_or(i64::MAX),
+            self.now()?
+        ).fetch_all(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        let tasks: Result<Vec<Task>, SchedulerError> =
+            records.into_iter().map(Task::from_db_task).collect();
+        Ok(tasks?)
+    }
+
+    async fn release_task(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query!(
+            "UPDATE tasks SET locked_by = NULL, locked_until = NULL WHERE id = $1 AND locked_by = $2",
+            id,
+            worker_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query!(
+            "UPDATE tasks SET locked_until = $3 + make_interval(secs => $2) WHERE id = $1 AND locked_by = $4",
+            id,
+            lease.as_secs_f64(),
+            self.now()?,
+            worker_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn next_due_time(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, crate::error::SchedulerError> {
+        let next_due_time = sqlx::query_scalar!(
+            "SELECT MIN(GREATEST(next_run, COALESCE(locked_until, next_run))) FROM tasks WHERE enabled = TRUE"
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        Ok(next_due_time.map(from_offset_datetime))
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), crate::error::SchedulerError> {
+        let db_run = run.to_db_run()?;
+
+        sqlx::query!(
+            "INSERT INTO task_runs (id, task_id, attempt, started_at, finished_at, outcome, error, output)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            db_run.id,
+            db_run.task_id,
+            db_run.attempt,
+            db_run.started_at,
+            db_run.finished_at,
+            db_run.outcome,
+            db_run.error,
+            db_run.output
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_task_runs(
+        &self,
+        task_id: Uuid,
+    ) -> Result<Vec<TaskRun>, crate::error::SchedulerError> {
+        let records = sqlx::query_as!(
+            TaskRunDb,
+            "SELECT id, task_id, attempt, started_at, finished_at, outcome, error, output FROM task_runs
+            WHERE task_id = $1
+            ORDER BY started_at, attempt",
+            task_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        records.into_iter().map(TaskRun::from_db_run).collect()
+    }
+
+    async fn save_dead_letter(
+        &self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let db_dead_letter = dead_letter.to_db_dead_letter()?;
+
+        sqlx::query!(
+            "INSERT INTO dead_letters (id, task_id, task, error, attempts, failed_at)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+            db_dead_letter.id,
+            db_dead_letter.task_id,
+            db_dead_letter.task,
+            db_dead_letter.error,
+            db_dead_letter.attempts,
+            db_dead_letter.failed_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_dead_letter(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DeadLetter>, crate::error::SchedulerError> {
+        let record = sqlx::query_as!(
+            DeadLetterDb,
+            "SELECT id, task_id, task, error, attempts, failed_at FROM dead_letters WHERE id = $1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        record.map(DeadLetter::from_db_dead_letter).transpose()
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, crate::error::SchedulerError> {
+        let records = sqlx::query_as!(
+            DeadLetterDb,
+            "SELECT id, task_id, task, error, attempts, failed_at FROM dead_letters ORDER BY failed_at"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        records
+            .into_iter()
+            .map(DeadLetter::from_db_dead_letter)
+            .collect()
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query!("DELETE FROM dead_letters WHERE id = $1", id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn watch_changes(
+        &self,
+        wakeup: Arc<Notify>,
+    ) -> Result<Option<JoinHandle<()>>, SchedulerError> {
+        let mut listener = PgListener::connect_with(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        listener
+            .listen(TASKS_CHANGED_CHANNEL)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        // Aborting the task drops the listener, which closes its connection.
+        let watcher = tokio::spawn(async move {
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        log::debug!("Task {} changed", notification.payload());
+                        wakeup.notify_one();
+                    }
+                    Ok(None) => {
+                        // The connection was lost and notifications may have been missed,
+                        // the listener reconnects on the next call.
+                        log::warn!("Lost connection while listening for task changes");
+                        wakeup.notify_one();
+                    }
+                    Err(e) => {
+                        log::error!("Error listening for task changes: {:?}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Some(watcher))
+    }
 }
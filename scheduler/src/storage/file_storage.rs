@@ -0,0 +1,471 @@
+use std::{
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::SchedulerError,
+    storage::{
+        base_storage::Storage,
+        in_memory_storage::InMemoryStorage,
+        task_query::{TaskPage, TaskQuery},
+    },
+    task::{
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        task_run::TaskRun,
+    },
+};
+
+static LOG_FILE_NAME: &str = "tasks.log";
+static COMPACTED_LOG_FILE_NAME: &str = "tasks.log.compacted";
+
+const DEFAULT_COMPACTION_THRESHOLD: usize = 1000;
+
+/// A change to the stored data, written as one JSON line of the log.
+#[derive(Serialize, Deserialize)]
+enum LogEntry {
+    TaskSaved(Task),
+    TaskDeleted(Uuid),
+    TaskRunRecorded(TaskRun),
+    DeadLetterSaved(DeadLetter),
+    DeadLetterDeleted(Uuid),
+}
+
+struct LogFile {
+    file: File,
+    /// Entries appended since the log was last compacted.
+    appended: usize,
+    /// Set when a failed append couldn't be cut off the log again, or when a compaction
+    /// couldn't make the new log durable. The log is then compacted, which rewrites it from
+    /// the applied entries, before anything else is written to it.
+    poisoned: bool,
+}
+
+/// Stores the tasks in an append-only log in a data directory, for running the scheduler
+/// on a single node without a database server.
+///
+/// Every change is appended to the log and synced to disk before it's applied, and the
+/// log is replayed into memory on start. An entry that was cut off by a crash is dropped
+/// when replaying. Once enough entries were appended, the log is compacted into one entry
+/// per stored record, see [`FileStorage::with_compaction_threshold`].
+///
+/// Leases only live in memory, so only one scheduler process may use a data directory.
+pub struct FileStorage {
+    dir: PathBuf,
+    state: InMemoryStorage,
+    log_file: Mutex<LogFile>,
+    compaction_threshold: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl FileStorage {
+    pub async fn new(data_dir: impl AsRef<Path>) -> Result<Self, SchedulerError> {
+        let dir = data_dir.as_ref().to_path_buf();
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // A compaction that didn't finish leaves the previous log in place.
+        match tokio::fs::remove_file(dir.join(COMPACTED_LOG_FILE_NAME)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        let state = InMemoryStorage::new();
+        let appended = replay(&dir.join(LOG_FILE_NAME), &state).await?;
+        let file = open_log(&dir).await?;
+
+        Ok(FileStorage {
+            dir,
+            state,
+            log_file: Mutex::new(LogFile {
+                file,
+                appended,
+                poisoned: false,
+            }),
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Compacts the log after this many appended entries. Defaults to 1000.
+    pub fn with_compaction_threshold(mut self, threshold: usize) -> Self {
+        self.compaction_threshold = threshold.max(1);
+        self
+    }
+
+    /// The clock deciding which tasks are due. Defaults to the system time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.state = std::mem::take(&mut self.state).with_clock(Arc::clone(&clock));
+        self.clock = clock;
+        self
+    }
+
+    async fn append(&self, log_file: &mut LogFile, entry: &LogEntry) -> Result<(), SchedulerError> {
+        if log_file.poisoned {
+            self.compact(log_file).await?;
+        }
+
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        // A partial entry would be in the middle of the log after the next append, where
+        // replaying it fails, so it's cut off again.
+        let len = log_file.file.metadata().await?.len();
+        let written = async {
+            log_file.file.write_all(&line).await?;
+            log_file.file.sync_data().await
+        }
+        .await;
+
+        if let Err(e) = written {
+            if let Err(truncate_error) = log_file.file.set_len(len).await {
+                log::error!(
+                    "Failed to cut a partial entry off {}: {:?}",
+                    self.dir.join(LOG_FILE_NAME).display(),
+                    truncate_error
+                );
+                log_file.poisoned = true;
+            }
+            return Err(e.into());
+        }
+
+        log_file.appended += 1;
+        Ok(())
+    }
+
+    /// Compacts the log once the threshold is reached. The entries were already written, so
+    /// a failed compaction is only logged and tried again after the next entry.
+    async fn compact_if_needed(&self, log_file: &mut LogFile) {
+        if log_file.appended < self.compaction_threshold {
+            return;
+        }
+
+        if let Err(e) = self.compact(log_file).await {
+            log::error!("Failed to compact {}: {:?}", self.dir.display(), e);
+        }
+    }
+
+    /// Writes the current state to a new log and replaces the old one with it.
+    async fn compact(&self, log_file: &mut LogFile) -> Result<(), SchedulerError> {
+        let mut entries: Vec<LogEntry> = Vec::new();
+        entries.extend(
+            self.state
+                .get_all_tasks()
+                .await?
+                .into_iter()
+                .map(LogEntry::TaskSaved),
+        );
+        entries.extend(
+            self.state
+                .all_task_runs()
+                .await
+                .into_iter()
+                .map(LogEntry::TaskRunRecorded),
+        );
+        entries.extend(
+            self.state
+                .get_dead_letters()
+                .await?
+                .into_iter()
+                .map(LogEntry::DeadLetterSaved),
+        );
+
+        let mut contents = Vec::new();
+        for entry in &entries {
+            serde_json::to_writer(&mut contents, entry)?;
+            contents.push(b'\n');
+        }
+
+        let compacted_path = self.dir.join(COMPACTED_LOG_FILE_NAME);
+        let mut compacted = File::create(&compacted_path).await?;
+        compacted.write_all(&contents).await?;
+        compacted.sync_all().await?;
+        drop(compacted);
+
+        // Opened before the rename, so that the appends go to the new log as soon as it
+        // replaced the old one, which is unlinked by then.
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&compacted_path)
+            .await?;
+        tokio::fs::rename(&compacted_path, self.dir.join(LOG_FILE_NAME)).await?;
+        log_file.file = file;
+        log_file.appended = 0;
+
+        // The rename may not survive a crash until the directory is synced, and the
+        // entries appended to the new log with it, so the log is compacted again first.
+        if let Err(e) = sync_dir(&self.dir).await {
+            log_file.poisoned = true;
+            return Err(e);
+        }
+
+        log_file.poisoned = false;
+        Ok(())
+    }
+
+    async fn write(&self, entry: LogEntry) -> Result<(), SchedulerError> {
+        let mut log_file = self.log_file.lock().await;
+        self.write_locked(&mut log_file, entry).await
+    }
+
+    /// Appends `entry` and applies it once it's on disk. Taking the locked log keeps the
+    /// order of the entries the order in which they were applied.
+    async fn write_locked(
+        &self,
+        log_file: &mut LogFile,
+        entry: LogEntry,
+    ) -> Result<(), SchedulerError> {
+        self.append(log_file, &entry).await?;
+        apply(&self.state, entry).await?;
+        self.compact_if_needed(log_file).await;
+        Ok(())
+    }
+
+    /// Applies `update` to the stored task and saves it with an incremented version, like
+    /// the `set_task_*` methods of the other storages.
+    async fn update_task(
+        &self,
+        id: Uuid,
+        update: impl FnOnce(&mut Task),
+    ) -> Result<(), SchedulerError> {
+        let mut log_file = self.log_file.lock().await;
+        let Some(mut task) = self.state.get_task(id).await? else {
+            return Ok(());
+        };
+
+        update(&mut task);
+        task.updated_at = self.clock.now();
+        task.version += 1;
+
+        self.write_locked(&mut log_file, LogEntry::TaskSaved(task))
+            .await
+    }
+}
+
+async fn open_log(dir: &Path) -> Result<File, SchedulerError> {
+    Ok(OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(LOG_FILE_NAME))
+        .await?)
+}
+
+/// Makes a rename in `dir` durable.
+async fn sync_dir(dir: &Path) -> Result<(), SchedulerError> {
+    File::open(dir).await?.sync_all().await?;
+    Ok(())
+}
+
+async fn apply(state: &InMemoryStorage, entry: LogEntry) -> Result<(), SchedulerError> {
+    match entry {
+        LogEntry::TaskSaved(task) => state.restore_task(task).await,
+        LogEntry::TaskDeleted(id) => state.delete_task(id).await?,
+        LogEntry::TaskRunRecorded(run) => state.record_task_run(run).await?,
+        LogEntry::DeadLetterSaved(dead_letter) => state.save_dead_letter(dead_letter).await?,
+        LogEntry::DeadLetterDeleted(id) => state.delete_dead_letter(id).await?,
+    }
+    Ok(())
+}
+
+/// Applies the entries of the log at `path` to `state` and returns how many there were.
+///
+/// The last entry is dropped and cut off the log if it's incomplete, since it was being
+/// written when the process stopped and was never acknowledged. An unreadable entry
+/// anywhere else means the log is corrupt.
+async fn replay(path: &Path, state: &InMemoryStorage) -> Result<usize, SchedulerError> {
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = 0;
+    let mut offset = 0;
+
+    while offset < contents.len() {
+        let rest = &contents[offset..];
+        let line_end = rest.iter().position(|byte| *byte == b'\n');
+        let entry = line_end.and_then(|end| serde_json::from_slice::<LogEntry>(&rest[..end]).ok());
+
+        match (line_end, entry) {
+            (Some(end), Some(entry)) => {
+                apply(state, entry).await?;
+                entries += 1;
+                offset += end + 1;
+            }
+            (Some(end), None) if offset + end + 1 < contents.len() => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Corrupt entry {} in {}", entries + 1, path.display()),
+                )
+                .into());
+            }
+            _ => {
+                log::warn!(
+                    "Dropping an incomplete entry of {} bytes at the end of {}",
+                    rest.len(),
+                    path.display()
+                );
+                let file = OpenOptions::new().write(true).open(path).await?;
+                file.set_len(offset as u64).await?;
+                file.sync_all().await?;
+                break;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn save_task(&self, task: Task) -> Result<Uuid, crate::error::SchedulerError> {
+        let mut log_file = self.log_file.lock().await;
+        let saved = self.state.get_task(task.id).await?;
+
+        if saved.as_ref().map_or(0, |saved| saved.version) != task.version {
+            return Err(SchedulerError::VersionConflict(task.id.to_string()));
+        }
+
+        let task = Task {
+            created_at: saved.map_or(task.created_at, |saved| saved.created_at),
+            updated_at: self.clock.now(),
+            version: task.version + 1,
+            ..task
+        };
+
+        let id = task.id;
+        self.write_locked(&mut log_file, LogEntry::TaskSaved(task))
+            .await?;
+        Ok(id)
+    }
+
+    async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, crate::error::SchedulerError> {
+        self.state.get_task(id).await
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        self.state.get_all_tasks().await
+    }
+
+    async fn delete_task(&self, id: uuid::Uuid) -> Result<(), crate::error::SchedulerError> {
+        self.write(LogEntry::TaskDeleted(id)).await
+    }
+
+    async fn set_task_enabled(
+        &self,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(id, |task| task.enabled = enabled).await
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(id, |task| task.next_run = next_run).await
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(id, |task| {
+            task.schedule = schedule;
+            task.next_run = next_run;
+        })
+        .await
+    }
+
+    async fn query_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<TaskPage, crate::error::SchedulerError> {
+        self.state.query_tasks(query).await
+    }
+
+    async fn get_ready_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        self.state.get_ready_tasks().await
+    }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        self.state.claim_ready_tasks(worker_id, lease, limit).await
+    }
+
+    async fn release_task(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.state.release_task(id, worker_id).await
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.state.renew_lease(id, worker_id, lease).await
+    }
+
+    async fn next_due_time(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, crate::error::SchedulerError> {
+        self.state.next_due_time().await
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), crate::error::SchedulerError> {
+        self.write(LogEntry::TaskRunRecorded(run)).await
+    }
+
+    async fn get_task_runs(
+        &self,
+        task_id: Uuid,
+    ) -> Result<Vec<TaskRun>, crate::error::SchedulerError> {
+        self.state.get_task_runs(task_id).await
+    }
+
+    async fn save_dead_letter(
+        &self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.write(LogEntry::DeadLetterSaved(dead_letter)).await
+    }
+
+    async fn get_dead_letter(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DeadLetter>, crate::error::SchedulerError> {
+        self.state.get_dead_letter(id).await
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, crate::error::SchedulerError> {
+        self.state.get_dead_letters().await
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), crate::error::SchedulerError> {
+        self.write(LogEntry::DeadLetterDeleted(id)).await
+    }
+}
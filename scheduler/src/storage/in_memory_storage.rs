@@ -1,21 +1,60 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use crate::{storage::base_storage::Storage, task::default::Task};
+use crate::{
+    clock::{Clock, SystemClock, after},
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskPage, TaskQuery},
+    },
+    task::{
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        task_run::TaskRun,
+    },
+};
+
+struct Lease {
+    worker_id: String,
+    locked_until: chrono::DateTime<chrono::Utc>,
+}
 
 pub struct InMemoryStorage {
     tasks: RwLock<HashMap<Uuid, Task>>,
+    leases: RwLock<HashMap<Uuid, Lease>>,
+    runs: RwLock<Vec<TaskRun>>,
+    dead_letters: RwLock<Vec<DeadLetter>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl InMemoryStorage {
     pub fn new() -> Self {
         InMemoryStorage {
             tasks: RwLock::new(HashMap::new()),
+            leases: RwLock::new(HashMap::new()),
+            runs: RwLock::new(Vec::new()),
+            dead_letters: RwLock::new(Vec::new()),
+            clock: Arc::new(SystemClock),
         }
     }
+
+    /// The clock deciding which tasks are due. Defaults to the system time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stores `task` as it is, without checking or incrementing its version.
+    pub(crate) async fn restore_task(&self, task: Task) {
+        self.tasks.write().await.insert(task.id, task);
+    }
+
+    pub(crate) async fn all_task_runs(&self) -> Vec<TaskRun> {
+        self.runs.read().await.clone()
+    }
 }
 
 impl Default for InMemoryStorage {
@@ -28,7 +67,24 @@ impl Default for InMemoryStorage {
 impl Storage for InMemoryStorage {
     async fn save_task(&self, task: Task) -> Result<Uuid, crate::error::SchedulerError> {
         let mut tasks = self.tasks.write().await;
-        tasks.insert(task.id, task.clone());
+        let saved = tasks.get(&task.id);
+
+        if saved.map_or(0, |saved| saved.version) != task.version {
+            return Err(crate::error::SchedulerError::VersionConflict(
+                task.id.to_string(),
+            ));
+        }
+
+        let created_at = saved.map_or(task.created_at, |saved| saved.created_at);
+        tasks.insert(
+            task.id,
+            Task {
+                created_at,
+                updated_at: self.clock.now(),
+                version: task.version + 1,
+                ..task.clone()
+            },
+        );
         Ok(task.id)
     }
 
@@ -46,12 +102,74 @@ impl Storage for InMemoryStorage {
     async fn delete_task(&self, id: uuid::Uuid) -> Result<(), crate::error::SchedulerError> {
         let mut tasks = self.tasks.write().await;
         tasks.remove(&id);
+        self.leases.write().await.remove(&id);
+        Ok(())
+    }
+
+    async fn set_task_enabled(
+        &self,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<(), crate::error::SchedulerError> {
+        if let Some(task) = self.tasks.write().await.get_mut(&id) {
+            task.enabled = enabled;
+            task.updated_at = self.clock.now();
+            task.version += 1;
+        }
+        Ok(())
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        if let Some(task) = self.tasks.write().await.get_mut(&id) {
+            task.next_run = next_run;
+            task.updated_at = self.clock.now();
+            task.version += 1;
+        }
         Ok(())
     }
 
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        if let Some(task) = self.tasks.write().await.get_mut(&id) {
+            task.schedule = schedule;
+            task.next_run = next_run;
+            task.updated_at = self.clock.now();
+            task.version += 1;
+        }
+        Ok(())
+    }
+
+    async fn query_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<TaskPage, crate::error::SchedulerError> {
+        let tasks = self.tasks.read().await;
+        let mut matching: Vec<Task> = tasks
+            .values()
+            .filter(|task| query.matches(task) && query.is_after_cursor(task))
+            .cloned()
+            .collect();
+
+        matching.sort_by_key(|task| (query.sort.key(task), task.id));
+        if query.sort.is_descending() {
+            matching.reverse();
+        }
+        matching.truncate(query.limit.saturating_add(1));
+
+        Ok(query.page_from(matching))
+    }
+
     async fn get_ready_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
         let tasks = self.tasks.read().await;
-        let now = chrono::Utc::now();
+        let now = self.clock.now();
         let ready_tasks: Vec<Task> = tasks
             .values()
             .filter(|task| task.enabled && task.next_run <= now)
@@ -59,4 +177,141 @@ impl Storage for InMemoryStorage {
             .collect();
         Ok(ready_tasks)
     }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let tasks = self.tasks.read().await;
+        let mut leases = self.leases.write().await;
+        let now = self.clock.now();
+        let locked_until = after(now, lease);
+
+        let mut claimed_tasks: Vec<Task> = tasks
+            .values()
+            .filter(|task| task.enabled && task.next_run <= now)
+            .filter(|task| {
+                leases
+                    .get(&task.id)
+                    .is_none_or(|lease| lease.locked_until <= now)
+            })
+            .cloned()
+            .collect();
+        claimed_tasks.sort_by_key(|task| task.next_run);
+        claimed_tasks.truncate(limit);
+
+        for task in &claimed_tasks {
+            leases.insert(
+                task.id,
+                Lease {
+                    worker_id: worker_id.to_string(),
+                    locked_until,
+                },
+            );
+        }
+
+        Ok(claimed_tasks)
+    }
+
+    async fn release_task(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let mut leases = self.leases.write().await;
+        if leases
+            .get(&id)
+            .is_some_and(|lease| lease.worker_id == worker_id)
+        {
+            leases.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let mut leases = self.leases.write().await;
+        let locked_until = after(self.clock.now(), lease);
+        if let Some(held) = leases
+            .get_mut(&id)
+            .filter(|held| held.worker_id == worker_id)
+        {
+            held.locked_until = locked_until;
+        }
+        Ok(())
+    }
+
+    async fn next_due_time(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, crate::error::SchedulerError> {
+        let tasks = self.tasks.read().await;
+        let leases = self.leases.read().await;
+
+        Ok(tasks
+            .values()
+            .filter(|task| task.enabled)
+            .map(|task| match leases.get(&task.id) {
+                Some(lease) => task.next_run.max(lease.locked_until),
+                None => task.next_run,
+            })
+            .min())
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), crate::error::SchedulerError> {
+        self.runs.write().await.push(run);
+        Ok(())
+    }
+
+    async fn get_task_runs(
+        &self,
+        task_id: Uuid,
+    ) -> Result<Vec<TaskRun>, crate::error::SchedulerError> {
+        let runs = self.runs.read().await;
+        let mut runs: Vec<TaskRun> = runs
+            .iter()
+            .filter(|run| run.task_id == task_id)
+            .cloned()
+            .collect();
+        runs.sort_by_key(|run| (run.started_at, run.attempt));
+        Ok(runs)
+    }
+
+    async fn save_dead_letter(
+        &self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.dead_letters.write().await.push(dead_letter);
+        Ok(())
+    }
+
+    async fn get_dead_letter(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DeadLetter>, crate::error::SchedulerError> {
+        let dead_letters = self.dead_letters.read().await;
+        Ok(dead_letters
+            .iter()
+            .find(|dead_letter| dead_letter.id == id)
+            .cloned())
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, crate::error::SchedulerError> {
+        let mut dead_letters = self.dead_letters.read().await.clone();
+        dead_letters.sort_by_key(|dead_letter| dead_letter.failed_at);
+        Ok(dead_letters)
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), crate::error::SchedulerError> {
+        self.dead_letters
+            .write()
+            .await
+            .retain(|dead_letter| dead_letter.id != id);
+        Ok(())
+    }
 }
@@ -1,3 +1,9 @@
 pub mod base_storage;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
 pub mod database_storage;
+pub mod file_storage;
 pub mod in_memory_storage;
+pub mod redis_storage;
+pub mod sqlite_storage;
+pub mod task_query;
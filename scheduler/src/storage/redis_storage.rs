@@ -0,0 +1,647 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use redis::{AsyncCommands, Script, aio::ConnectionManager};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::SchedulerError,
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskPage, TaskQuery},
+    },
+    task::{
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        task_run::TaskRun,
+    },
+};
+
+static DEFAULT_KEY_PREFIX: &str = "scheduler";
+
+/// How many tasks of the due index `next_due_time` reads at once.
+const NEXT_DUE_TIME_CHUNK_SIZE: usize = 100;
+
+/// Hash fields that are only used for indexing and leasing, not part of the task.
+static NEXT_RUN_MS_FIELD: &str = "next_run_ms";
+static LOCKED_BY_FIELD: &str = "locked_by";
+
+/// Keeps the due index in sync with the task hash: enabled tasks are in it, scored by
+/// `next_run` in unix milliseconds.
+static SYNC_DUE_INDEX: &str = r#"
+local function sync_due_index(task_key, due_key, id)
+    local fields = redis.call('HMGET', task_key, 'enabled', 'next_run_ms')
+    if fields[1] == 'true' then
+        redis.call('ZADD', due_key, fields[2], id)
+    else
+        redis.call('ZREM', due_key, id)
+    end
+end
+"#;
+
+/// KEYS: task, task ids, due index. ARGV: id, expected version, created_at, fields.
+static SAVE_TASK: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(&format!(
+        "{}{}",
+        SYNC_DUE_INDEX,
+        r#"
+local version = tonumber(redis.call('HGET', KEYS[1], 'version') or '0')
+if version ~= tonumber(ARGV[2]) then
+    return 0
+end
+
+redis.call('HSETNX', KEYS[1], 'created_at', ARGV[3])
+redis.call('HSET', KEYS[1], unpack(ARGV, 4))
+redis.call('SADD', KEYS[2], ARGV[1])
+sync_due_index(KEYS[1], KEYS[3], ARGV[1])
+return 1
+"#
+    ))
+});
+
+/// KEYS: task, due index. ARGV: id, fields.
+static UPDATE_TASK: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(&format!(
+        "{}{}",
+        SYNC_DUE_INDEX,
+        r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+
+redis.call('HSET', KEYS[1], unpack(ARGV, 2))
+redis.call('HINCRBY', KEYS[1], 'version', 1)
+sync_due_index(KEYS[1], KEYS[2], ARGV[1])
+return 1
+"#
+    ))
+});
+
+/// KEYS: task, task ids, due index, leases. ARGV: id.
+static DELETE_TASK: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+redis.call('DEL', KEYS[1])
+redis.call('SREM', KEYS[2], ARGV[1])
+redis.call('ZREM', KEYS[3], ARGV[1])
+redis.call('ZREM', KEYS[4], ARGV[1])
+return 1
+"#,
+    )
+});
+
+/// KEYS: due index, leases. ARGV: now, locked until, limit, worker id, task key prefix.
+///
+/// The task keys are built in the script, since the claimed tasks aren't known up front.
+static CLAIM_READY_TASKS: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local now = tonumber(ARGV[1])
+local limit = tonumber(ARGV[3])
+local claimed = {}
+if limit <= 0 then
+    return claimed
+end
+
+for _, id in ipairs(redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', now)) do
+    local locked_until = redis.call('ZSCORE', KEYS[2], id)
+    if not locked_until or tonumber(locked_until) <= now then
+        redis.call('ZADD', KEYS[2], ARGV[2], id)
+        redis.call('HSET', ARGV[5] .. id, 'locked_by', ARGV[4])
+        table.insert(claimed, id)
+        if #claimed >= limit then
+            break
+        end
+    end
+end
+return claimed
+"#,
+    )
+});
+
+/// KEYS: task, leases. ARGV: id, worker id.
+static RELEASE_TASK: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+if redis.call('HGET', KEYS[1], 'locked_by') == ARGV[2] then
+    redis.call('HDEL', KEYS[1], 'locked_by')
+    redis.call('ZREM', KEYS[2], ARGV[1])
+end
+return 1
+"#,
+    )
+});
+
+/// KEYS: task, leases. ARGV: id, worker id, locked until.
+static RENEW_LEASE: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+if redis.call('HGET', KEYS[1], 'locked_by') == ARGV[2] then
+    redis.call('ZADD', KEYS[2], ARGV[3], ARGV[1])
+end
+return 1
+"#,
+    )
+});
+
+/// KEYS: due index, leases. ARGV: chunk size. Returns the earliest time a task can be
+/// claimed, in unix milliseconds.
+///
+/// The due index is walked in chunks, earliest first, and only up to the first task that
+/// isn't leased, since no task after it can be due earlier.
+static NEXT_DUE_TIME: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local chunk_size = tonumber(ARGV[1])
+local next_due = false
+local start = 0
+while true do
+    local due = redis.call('ZRANGE', KEYS[1], start, start + chunk_size - 1, 'WITHSCORES')
+    for i = 1, #due, 2 do
+        local next_run = tonumber(due[i + 1])
+        if next_due and next_run >= next_due then
+            return next_due
+        end
+
+        local locked_until = tonumber(redis.call('ZSCORE', KEYS[2], due[i]) or next_run)
+        local due_at = math.max(next_run, locked_until)
+        if not next_due or due_at < next_due then
+            next_due = due_at
+        end
+    end
+
+    if #due < chunk_size * 2 then
+        return next_due
+    end
+    start = start + chunk_size
+end
+"#,
+    )
+});
+
+/// Stores the tasks in Redis, so that several scheduler processes can share them.
+///
+/// Every task is a hash with one JSON encoded field per task field. The ids of the enabled
+/// tasks are kept in a sorted set scored by `next_run`, which is where ready tasks are
+/// read and claimed from. Writes that touch several keys run as Lua scripts, so they are
+/// atomic. Queries load every task, Redis has no secondary indexes to filter them with.
+pub struct RedisStorage {
+    connection: ConnectionManager,
+    key_prefix: String,
+    clock: Arc<dyn Clock>,
+}
+
+impl RedisStorage {
+    pub async fn new(redis_url: &str) -> Result<Self, SchedulerError> {
+        let client = redis::Client::open(redis_url).map_err(to_database_error)?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .map_err(to_database_error)?;
+
+        Ok(RedisStorage {
+            connection,
+            key_prefix: DEFAULT_KEY_PREFIX.to_string(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Prefixes every key, so that several schedulers can share a Redis database. Defaults
+    /// to `scheduler`.
+    pub fn with_key_prefix(mut self, key_prefix: impl Into<String>) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+
+    /// The clock deciding which tasks are due, its time is passed to the scripts. Defaults
+    /// to the system time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn task_key_prefix(&self) -> String {
+        format!("{}:task:", self.key_prefix)
+    }
+
+    fn task_key(&self, id: Uuid) -> String {
+        format!("{}{}", self.task_key_prefix(), id)
+    }
+
+    fn task_runs_key(&self, id: Uuid) -> String {
+        format!("{}:task_runs:{}", self.key_prefix, id)
+    }
+
+    fn task_ids_key(&self) -> String {
+        format!("{}:tasks", self.key_prefix)
+    }
+
+    fn due_key(&self) -> String {
+        format!("{}:due", self.key_prefix)
+    }
+
+    fn leases_key(&self) -> String {
+        format!("{}:leases", self.key_prefix)
+    }
+
+    fn dead_letters_key(&self) -> String {
+        format!("{}:dead_letters", self.key_prefix)
+    }
+
+    async fn get_tasks(&self, ids: &[String]) -> Result<Vec<Task>, SchedulerError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut pipeline = redis::pipe();
+        for id in ids {
+            pipeline.hgetall(format!("{}{}", self.task_key_prefix(), id));
+        }
+
+        let hashes: Vec<HashMap<String, String>> = pipeline
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+
+        // Tasks deleted while reading have an empty hash.
+        hashes
+            .into_iter()
+            .filter(|hash| !hash.is_empty())
+            .map(from_hash)
+            .collect()
+    }
+
+    async fn update_task(
+        &self,
+        id: Uuid,
+        fields: Vec<(&str, String)>,
+    ) -> Result<(), SchedulerError> {
+        let mut invocation = UPDATE_TASK.prepare_invoke();
+        invocation
+            .key(self.task_key(id))
+            .key(self.due_key())
+            .arg(id.to_string());
+        for (name, value) in fields {
+            invocation.arg(name).arg(value);
+        }
+        invocation
+            .arg("updated_at")
+            .arg(to_json(&self.clock.now())?);
+
+        invocation
+            .invoke_async::<i64>(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+}
+
+fn to_database_error(e: redis::RedisError) -> SchedulerError {
+    SchedulerError::DatabaseError(e.to_string())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, SchedulerError> {
+    Ok(serde_json::to_string(value)?)
+}
+
+/// The hash fields of `task`, other than `created_at` which is only set on insert.
+fn to_hash(task: &Task) -> Result<Vec<(String, String)>, SchedulerError> {
+    let Value::Object(fields) = serde_json::to_value(task)? else {
+        return Err(SchedulerError::DatabaseError(
+            "Task didn't serialize to an object".to_string(),
+        ));
+    };
+
+    let mut hash: Vec<(String, String)> = fields
+        .into_iter()
+        .filter(|(name, _)| name != "created_at")
+        .map(|(name, value)| (name, value.to_string()))
+        .collect();
+    hash.push((
+        NEXT_RUN_MS_FIELD.to_string(),
+        task.next_run.timestamp_millis().to_string(),
+    ));
+    Ok(hash)
+}
+
+fn from_hash(hash: HashMap<String, String>) -> Result<Task, SchedulerError> {
+    let fields = hash
+        .into_iter()
+        .filter(|(name, _)| name != NEXT_RUN_MS_FIELD && name != LOCKED_BY_FIELD)
+        .map(|(name, value)| Ok((name, serde_json::from_str(&value)?)))
+        .collect::<Result<Map<String, Value>, SchedulerError>>()?;
+
+    Ok(serde_json::from_value(Value::Object(fields))?)
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn save_task(&self, task: Task) -> Result<Uuid, crate::error::SchedulerError> {
+        let expected_version = task.version;
+        let saved_task = Task {
+            updated_at: self.clock.now(),
+            version: task.version + 1,
+            ..task.clone()
+        };
+
+        let mut invocation = SAVE_TASK.prepare_invoke();
+        invocation
+            .key(self.task_key(task.id))
+            .key(self.task_ids_key())
+            .key(self.due_key())
+            .arg(task.id.to_string())
+            .arg(expected_version)
+            .arg(to_json(&task.created_at)?);
+        for (name, value) in to_hash(&saved_task)? {
+            invocation.arg(name).arg(value);
+        }
+
+        let saved: i64 = invocation
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+
+        if saved == 0 {
+            return Err(SchedulerError::VersionConflict(task.id.to_string()));
+        }
+        Ok(task.id)
+    }
+
+    async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, crate::error::SchedulerError> {
+        let hash: HashMap<String, String> = self
+            .connection
+            .clone()
+            .hgetall(self.task_key(id))
+            .await
+            .map_err(to_database_error)?;
+
+        if hash.is_empty() {
+            return Ok(None);
+        }
+        from_hash(hash).map(Some)
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let ids: Vec<String> = self
+            .connection
+            .clone()
+            .smembers(self.task_ids_key())
+            .await
+            .map_err(to_database_error)?;
+
+        self.get_tasks(&ids).await
+    }
+
+    async fn delete_task(&self, id: uuid::Uuid) -> Result<(), crate::error::SchedulerError> {
+        DELETE_TASK
+            .key(self.task_key(id))
+            .key(self.task_ids_key())
+            .key(self.due_key())
+            .key(self.leases_key())
+            .arg(id.to_string())
+            .invoke_async::<i64>(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+
+    async fn set_task_enabled(
+        &self,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(id, vec![("enabled", to_json(&enabled)?)])
+            .await
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(
+            id,
+            vec![
+                ("next_run", to_json(&next_run)?),
+                (NEXT_RUN_MS_FIELD, next_run.timestamp_millis().to_string()),
+            ],
+        )
+        .await
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.update_task(
+            id,
+            vec![
+                ("schedule", to_json(&schedule)?),
+                ("next_run", to_json(&next_run)?),
+                (NEXT_RUN_MS_FIELD, next_run.timestamp_millis().to_string()),
+            ],
+        )
+        .await
+    }
+
+    async fn query_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<TaskPage, crate::error::SchedulerError> {
+        let mut matching: Vec<Task> = self
+            .get_all_tasks()
+            .await?
+            .into_iter()
+            .filter(|task| query.matches(task) && query.is_after_cursor(task))
+            .collect();
+
+        matching.sort_by_key(|task| (query.sort.key(task), task.id));
+        if query.sort.is_descending() {
+            matching.reverse();
+        }
+        matching.truncate(query.limit.saturating_add(1));
+
+        Ok(query.page_from(matching))
+    }
+
+    async fn get_ready_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let ids: Vec<String> = self
+            .connection
+            .clone()
+            .zrangebyscore(self.due_key(), "-inf", self.clock.now().timestamp_millis())
+            .await
+            .map_err(to_database_error)?;
+
+        self.get_tasks(&ids).await
+    }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let now = self.clock.now().timestamp_millis();
+        let locked_until = now.saturating_add(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+
+        let ids: Vec<String> = CLAIM_READY_TASKS
+            .key(self.due_key())
+            .key(self.leases_key())
+            .arg(now)
+            .arg(locked_until)
+            .arg(limit)
+            .arg(worker_id)
+            .arg(self.task_key_prefix())
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+
+        let mut tasks = self.get_tasks(&ids).await?;
+        tasks.sort_by_key(|task| task.next_run);
+        Ok(tasks)
+    }
+
+    async fn release_task(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+    ) -> Result<(), crate::error::SchedulerError> {
+        RELEASE_TASK
+            .key(self.task_key(id))
+            .key(self.leases_key())
+            .arg(id.to_string())
+            .arg(worker_id)
+            .invoke_async::<i64>(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let now = self.clock.now().timestamp_millis();
+        let locked_until = now.saturating_add(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+
+        RENEW_LEASE
+            .key(self.task_key(id))
+            .key(self.leases_key())
+            .arg(id.to_string())
+            .arg(worker_id)
+            .arg(locked_until)
+            .invoke_async::<i64>(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+
+    async fn next_due_time(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, crate::error::SchedulerError> {
+        let next_due_time: Option<i64> = NEXT_DUE_TIME
+            .key(self.due_key())
+            .key(self.leases_key())
+            .arg(NEXT_DUE_TIME_CHUNK_SIZE)
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .map_err(to_database_error)?;
+
+        Ok(next_due_time.and_then(DateTime::from_timestamp_millis))
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), crate::error::SchedulerError> {
+        self.connection
+            .clone()
+            .rpush::<_, _, ()>(self.task_runs_key(run.task_id), to_json(&run)?)
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+
+    async fn get_task_runs(
+        &self,
+        task_id: Uuid,
+    ) -> Result<Vec<TaskRun>, crate::error::SchedulerError> {
+        let runs: Vec<String> = self
+            .connection
+            .clone()
+            .lrange(self.task_runs_key(task_id), 0, -1)
+            .await
+            .map_err(to_database_error)?;
+
+        let mut runs = runs
+            .iter()
+            .map(|run| Ok(serde_json::from_str::<TaskRun>(run)?))
+            .collect::<Result<Vec<TaskRun>, SchedulerError>>()?;
+        runs.sort_by_key(|run| (run.started_at, run.attempt));
+        Ok(runs)
+    }
+
+    async fn save_dead_letter(
+        &self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), crate::error::SchedulerError> {
+        self.connection
+            .clone()
+            .hset::<_, _, _, ()>(
+                self.dead_letters_key(),
+                dead_letter.id.to_string(),
+                to_json(&dead_letter)?,
+            )
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+
+    async fn get_dead_letter(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DeadLetter>, crate::error::SchedulerError> {
+        let dead_letter: Option<String> = self
+            .connection
+            .clone()
+            .hget(self.dead_letters_key(), id.to_string())
+            .await
+            .map_err(to_database_error)?;
+
+        Ok(dead_letter
+            .map(|dead_letter| serde_json::from_str(&dead_letter))
+            .transpose()?)
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, crate::error::SchedulerError> {
+        let dead_letters: Vec<String> = self
+            .connection
+            .clone()
+            .hvals(self.dead_letters_key())
+            .await
+            .map_err(to_database_error)?;
+
+        let mut dead_letters = dead_letters
+            .iter()
+            .map(|dead_letter| Ok(serde_json::from_str::<DeadLetter>(dead_letter)?))
+            .collect::<Result<Vec<DeadLetter>, SchedulerError>>()?;
+        dead_letters.sort_by_key(|dead_letter| dead_letter.failed_at);
+        Ok(dead_letters)
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), crate::error::SchedulerError> {
+        self.connection
+            .clone()
+            .hdel::<_, _, ()>(self.dead_letters_key(), id.to_string())
+            .await
+            .map_err(to_database_error)?;
+        Ok(())
+    }
+}
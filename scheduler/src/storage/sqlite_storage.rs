@@ -0,0 +1,685 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    QueryBuilder, Sqlite, SqlitePool,
+    query::Query,
+    sqlite::{SqliteArguments, SqliteConnectOptions, SqliteJournalMode},
+    types::{Json, JsonValue, time::OffsetDateTime},
+};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::SchedulerError,
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskPage, TaskQuery, TaskSort},
+    },
+    task::{
+        dead_letter::{DeadLetter, DeadLetterDb},
+        default::{Task, TaskDb, TaskType, to_offset_datetime},
+        task_run::{TaskRun, TaskRunDb},
+    },
+};
+
+static TASK_COLUMNS: &str = "id, schedule_type, last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, created_at, updated_at, version";
+
+/// Stores the tasks in a SQLite database, for running the scheduler without a Postgres
+/// server. The database has its own migrations, see [`crate::db::migrator::Migrator`].
+///
+/// Every connection of an in-memory database (`sqlite::memory:`) gets its own empty
+/// database, so use a file, e.g. `sqlite://tasks.db`.
+pub struct SqliteStorage {
+    pub pool: SqlitePool,
+    clock: Arc<dyn Clock>,
+}
+
+impl SqliteStorage {
+    pub async fn new(database_url: &str) -> Result<Self, SchedulerError> {
+        let pool = connect(database_url).await?;
+        Ok(SqliteStorage {
+            pool,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// The clock deciding which tasks are due, its time is passed to the queries. Defaults
+    /// to the system time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.clock.now().timestamp_millis()
+    }
+}
+
+/// Connects to the database, creating the file if it doesn't exist yet.
+pub(crate) async fn connect(database_url: &str) -> Result<SqlitePool, SchedulerError> {
+    let options = SqliteConnectOptions::from_str(database_url)
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+
+    SqlitePool::connect_with(options)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))
+}
+
+/// A task as stored in SQLite, which has no timestamp, JSON or array types. Timestamps are
+/// unix milliseconds, JSON values and tags are stored as text.
+#[derive(sqlx::FromRow)]
+struct SqliteTaskRow {
+    id: Uuid,
+    schedule_type: i16,
+    last_run: Option<i64>,
+    next_run: i64,
+    retry_count: i32,
+    max_retries: i32,
+    retry_delay: i32,
+    enabled: bool,
+    action: Json<JsonValue>,
+    start_date: Option<i64>,
+    end_date: Option<i64>,
+    cron_expression: Option<String>,
+    timezone: String,
+    delay_between_runs: Option<i64>,
+    retry_policy: Option<Json<JsonValue>>,
+    execution_timeout: Option<i64>,
+    misfire_policy: Json<JsonValue>,
+    title: Option<String>,
+    description: Option<String>,
+    created_by: Option<String>,
+    assignee: Option<String>,
+    chat_id: Option<i64>,
+    tags: Json<Vec<String>>,
+    created_at: i64,
+    updated_at: i64,
+    version: i64,
+}
+
+impl SqliteTaskRow {
+    fn from_task(task: &Task) -> Result<Self, SchedulerError> {
+        let db_task = task.to_db_task()?;
+
+        Ok(SqliteTaskRow {
+            id: db_task.id,
+            schedule_type: db_task.schedule_type,
+            last_run: db_task.last_run.map(to_millis),
+            next_run: to_millis(db_task.next_run),
+            retry_count: db_task.retry_count,
+            max_retries: db_task.max_retries,
+            retry_delay: db_task.retry_delay,
+            enabled: db_task.enabled,
+            action: Json(db_task.action),
+            start_date: db_task.start_date.map(to_millis),
+            end_date: db_task.end_date.map(to_millis),
+            cron_expression: db_task.cron_expression,
+            timezone: db_task.timezone,
+            delay_between_runs: db_task.delay_between_runs,
+            retry_policy: db_task.retry_policy.map(Json),
+            execution_timeout: db_task.execution_timeout,
+            misfire_policy: Json(db_task.misfire_policy),
+            title: db_task.title,
+            description: db_task.description,
+            created_by: db_task.created_by,
+            assignee: db_task.assignee,
+            chat_id: db_task.chat_id,
+            tags: Json(db_task.tags),
+            created_at: to_millis(db_task.created_at),
+            updated_at: to_millis(db_task.updated_at),
+            version: db_task.version,
+        })
+    }
+
+    fn into_task(self) -> Result<Task, SchedulerError> {
+        Task::from_db_task(TaskDb {
+            id: self.id,
+            schedule_type: self.schedule_type,
+            last_run: self.last_run.map(from_millis).transpose()?,
+            next_run: from_millis(self.next_run)?,
+            retry_count: self.retry_count,
+            max_retries: self.max_retries,
+            retry_delay: self.retry_delay,
+            enabled: self.enabled,
+            action: self.action.0,
+            start_date: self.start_date.map(from_millis).transpose()?,
+            end_date: self.end_date.map(from_millis).transpose()?,
+            cron_expression: self.cron_expression,
+            timezone: self.timezone,
+            delay_between_runs: self.delay_between_runs,
+            retry_policy: self.retry_policy.map(|policy| policy.0),
+            execution_timeout: self.execution_timeout,
+            misfire_policy: self.misfire_policy.0,
+            title: self.title,
+            description: self.description,
+            created_by: self.created_by,
+            assignee: self.assignee,
+            chat_id: self.chat_id,
+            tags: self.tags.0,
+            created_at: from_millis(self.created_at)?,
+            updated_at: from_millis(self.updated_at)?,
+            version: self.version,
+        })
+    }
+
+    /// Binds the columns from `schedule_type` to `tags`, in the order of [`TASK_COLUMNS`].
+    fn bind_columns<'q>(
+        &'q self,
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    ) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        query
+            .bind(self.schedule_type)
+            .bind(self.last_run)
+            .bind(self.next_run)
+            .bind(self.retry_count)
+            .bind(self.max_retries)
+            .bind(self.retry_delay)
+            .bind(self.enabled)
+            .bind(&self.action)
+            .bind(self.start_date)
+            .bind(self.end_date)
+            .bind(&self.cron_expression)
+            .bind(&self.timezone)
+            .bind(self.delay_between_runs)
+            .bind(&self.retry_policy)
+            .bind(self.execution_timeout)
+            .bind(&self.misfire_policy)
+            .bind(&self.title)
+            .bind(&self.description)
+            .bind(&self.created_by)
+            .bind(&self.assignee)
+            .bind(self.chat_id)
+            .bind(&self.tags)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SqliteTaskRunRow {
+    id: Uuid,
+    task_id: Uuid,
+    attempt: i32,
+    started_at: i64,
+    finished_at: i64,
+    outcome: i16,
+    error: Option<String>,
+    output: Option<String>,
+}
+
+impl SqliteTaskRunRow {
+    fn into_task_run(self) -> Result<TaskRun, SchedulerError> {
+        TaskRun::from_db_run(TaskRunDb {
+            id: self.id,
+            task_id: self.task_id,
+            attempt: self.attempt,
+            started_at: from_millis(self.started_at)?,
+            finished_at: from_millis(self.finished_at)?,
+            outcome: self.outcome,
+            error: self.error,
+            output: self.output,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SqliteDeadLetterRow {
+    id: Uuid,
+    task_id: Uuid,
+    task: Json<JsonValue>,
+    error: String,
+    attempts: i32,
+    failed_at: i64,
+}
+
+impl SqliteDeadLetterRow {
+    fn into_dead_letter(self) -> Result<DeadLetter, SchedulerError> {
+        DeadLetter::from_db_dead_letter(DeadLetterDb {
+            id: self.id,
+            task_id: self.task_id,
+            task: self.task.0,
+            error: self.error,
+            attempts: self.attempts,
+            failed_at: from_millis(self.failed_at)?,
+        })
+    }
+}
+
+fn to_millis(odt: OffsetDateTime) -> i64 {
+    (odt.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+fn from_millis(millis: i64) -> Result<OffsetDateTime, SchedulerError> {
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))
+}
+
+/// Stores `dt` with the same precision as the Postgres backend.
+fn datetime_to_millis(dt: DateTime<Utc>) -> Result<i64, SchedulerError> {
+    Ok(to_millis(to_offset_datetime(dt)?))
+}
+
+fn into_tasks(rows: Vec<SqliteTaskRow>) -> Result<Vec<Task>, SchedulerError> {
+    rows.into_iter().map(SqliteTaskRow::into_task).collect()
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn save_task(&self, task: Task) -> Result<Uuid, crate::error::SchedulerError> {
+        let row = SqliteTaskRow::from_task(&task)?;
+
+        // Tasks that were never saved are inserted, the others are only updated if nobody
+        // else saved them since they were read.
+        let result = if row.version == 0 {
+            let query = sqlx::query(
+                "INSERT INTO tasks (schedule_type, last_run, next_run, retry_count, max_retries, retry_delay, enabled, action, start_date, end_date, cron_expression, timezone, delay_between_runs, retry_policy, execution_timeout, misfire_policy, title, description, created_by, assignee, chat_id, tags, id, created_at, updated_at, version)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+                ON CONFLICT (id) DO NOTHING",
+            );
+            row.bind_columns(query)
+                .bind(row.id)
+                .bind(row.created_at)
+                .bind(self.now_millis())
+        } else {
+            let query = sqlx::query(
+                "UPDATE tasks SET
+                    schedule_type = ?,
+                    last_run = ?,
+                    next_run = ?,
+                    retry_count = ?,
+                    max_retries = ?,
+                    retry_delay = ?,
+                    enabled = ?,
+                    action = ?,
+                    start_date = ?,
+                    end_date = ?,
+                    cron_expression = ?,
+                    timezone = ?,
+                    delay_between_runs = ?,
+                    retry_policy = ?,
+                    execution_timeout = ?,
+                    misfire_policy = ?,
+                    title = ?,
+                    description = ?,
+                    created_by = ?,
+                    assignee = ?,
+                    chat_id = ?,
+                    tags = ?,
+                    updated_at = ?,
+                    version = version + 1
+                WHERE id = ? AND version = ?",
+            );
+            row.bind_columns(query)
+                .bind(self.now_millis())
+                .bind(row.id)
+                .bind(row.version)
+        }
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SchedulerError::VersionConflict(task.id.to_string()));
+        }
+        Ok(task.id)
+    }
+
+    async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, crate::error::SchedulerError> {
+        let row = sqlx::query_as::<_, SqliteTaskRow>(&format!(
+            "SELECT {} FROM tasks WHERE id = ?",
+            TASK_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        row.map(SqliteTaskRow::into_task).transpose()
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let rows =
+            sqlx::query_as::<_, SqliteTaskRow>(&format!("SELECT {} FROM tasks", TASK_COLUMNS))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        into_tasks(rows)
+    }
+
+    async fn delete_task(&self, id: uuid::Uuid) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query("DELETE FROM tasks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_task_enabled(
+        &self,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query(
+            "UPDATE tasks SET enabled = ?, updated_at = ?, version = version + 1 WHERE id = ?",
+        )
+        .bind(enabled)
+        .bind(self.now_millis())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query(
+            "UPDATE tasks SET next_run = ?, updated_at = ?, version = version + 1 WHERE id = ?",
+        )
+        .bind(datetime_to_millis(next_run)?)
+        .bind(self.now_millis())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let (schedule_type, start_date, end_date, cron_expression) = schedule.to_db_columns()?;
+
+        sqlx::query(
+            "UPDATE tasks SET schedule_type = ?, start_date = ?, end_date = ?, cron_expression = ?, next_run = ?, updated_at = ?, version = version + 1
+            WHERE id = ?",
+        )
+        .bind(schedule_type)
+        .bind(start_date.map(to_millis))
+        .bind(end_date.map(to_millis))
+        .bind(cron_expression)
+        .bind(datetime_to_millis(next_run)?)
+        .bind(self.now_millis())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn query_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<TaskPage, crate::error::SchedulerError> {
+        let mut builder =
+            QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM tasks WHERE TRUE", TASK_COLUMNS));
+
+        if let Some(chat_id) = query.chat_id {
+            builder.push(" AND chat_id = ").push_bind(chat_id);
+        }
+        if let Some(assignee) = &query.assignee {
+            builder.push(" AND assignee = ").push_bind(assignee.clone());
+        }
+        if let Some(created_by) = &query.created_by {
+            builder
+                .push(" AND created_by = ")
+                .push_bind(created_by.clone());
+        }
+        if let Some(enabled) = query.enabled {
+            builder.push(" AND enabled = ").push_bind(enabled);
+        }
+        if let Some(from) = query.next_run_from {
+            builder
+                .push(" AND next_run >= ")
+                .push_bind(datetime_to_millis(from)?);
+        }
+        if let Some(until) = query.next_run_until {
+            builder
+                .push(" AND next_run < ")
+                .push_bind(datetime_to_millis(until)?);
+        }
+        for tag in &query.tags {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM json_each(tasks.tags) WHERE json_each.value = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+
+        let (column, direction, comparison) = match query.sort {
+            TaskSort::NextRunAsc => ("next_run", "ASC", ">"),
+            TaskSort::NextRunDesc => ("next_run", "DESC", "<"),
+            TaskSort::CreatedAtAsc => ("created_at", "ASC", ">"),
+            TaskSort::CreatedAtDesc => ("created_at", "DESC", "<"),
+        };
+
+        if let Some(cursor) = &query.cursor {
+            builder
+                .push(format!(" AND ({}, id) {} (", column, comparison))
+                .push_bind(datetime_to_millis(cursor.key())?)
+                .push(", ")
+                .push_bind(cursor.id())
+                .push(")");
+        }
+
+        builder
+            .push(format!(
+                " ORDER BY {} {}, id {} LIMIT ",
+                column, direction, direction
+            ))
+            .push_bind(i64::try_from(query.limit.saturating_add(1)).unwrap_or(i64::MAX));
+
+        let rows = builder
+            .build_query_as::<SqliteTaskRow>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        Ok(query.page_from(into_tasks(rows)?))
+    }
+
+    async fn get_ready_tasks(&self) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let rows = sqlx::query_as::<_, SqliteTaskRow>(&format!(
+            "SELECT {} FROM tasks WHERE next_run <= ? AND enabled = TRUE",
+            TASK_COLUMNS
+        ))
+        .bind(self.now_millis())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        into_tasks(rows)
+    }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, crate::error::SchedulerError> {
+        let now = self.now_millis();
+        let locked_until = now.saturating_add(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+
+        // SQLite runs one write at a time, so the tasks can't be claimed by another worker
+        // between the select and the update.
+        let rows = sqlx::query_as::<_, SqliteTaskRow>(&format!(
+            "UPDATE tasks SET locked_by = ?, locked_until = ?
+            WHERE id IN (
+                SELECT id FROM tasks
+                WHERE next_run <= ? AND enabled = TRUE
+                    AND (locked_until IS NULL OR locked_until <= ?)
+                ORDER BY next_run
+                LIMIT ?
+            )
+            RETURNING {}",
+            TASK_COLUMNS
+        ))
+        .bind(worker_id)
+        .bind(locked_until)
+        .bind(now)
+        .bind(now)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        into_tasks(rows)
+    }
+
+    async fn release_task(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+    ) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query(
+            "UPDATE tasks SET locked_by = NULL, locked_until = NULL WHERE id = ? AND locked_by = ?",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let locked_until = self
+            .now_millis()
+            .saturating_add(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+
+        sqlx::query("UPDATE tasks SET locked_until = ? WHERE id = ? AND locked_by = ?")
+            .bind(locked_until)
+            .bind(id)
+            .bind(worker_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn next_due_time(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, crate::error::SchedulerError> {
+        let next_due_time: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(MAX(next_run, COALESCE(locked_until, next_run))) FROM tasks WHERE enabled = TRUE",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        Ok(next_due_time.and_then(DateTime::from_timestamp_millis))
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), crate::error::SchedulerError> {
+        let db_run = run.to_db_run()?;
+
+        sqlx::query(
+            "INSERT INTO task_runs (id, task_id, attempt, started_at, finished_at, outcome, error, output)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(db_run.id)
+        .bind(db_run.task_id)
+        .bind(db_run.attempt)
+        .bind(to_millis(db_run.started_at))
+        .bind(to_millis(db_run.finished_at))
+        .bind(db_run.outcome)
+        .bind(db_run.error)
+        .bind(db_run.output)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_task_runs(
+        &self,
+        task_id: Uuid,
+    ) -> Result<Vec<TaskRun>, crate::error::SchedulerError> {
+        let rows = sqlx::query_as::<_, SqliteTaskRunRow>(
+            "SELECT id, task_id, attempt, started_at, finished_at, outcome, error, output FROM task_runs
+            WHERE task_id = ?
+            ORDER BY started_at, attempt",
+        )
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(SqliteTaskRunRow::into_task_run)
+            .collect()
+    }
+
+    async fn save_dead_letter(
+        &self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), crate::error::SchedulerError> {
+        let db_dead_letter = dead_letter.to_db_dead_letter()?;
+
+        sqlx::query(
+            "INSERT INTO dead_letters (id, task_id, task, error, attempts, failed_at)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(db_dead_letter.id)
+        .bind(db_dead_letter.task_id)
+        .bind(Json(db_dead_letter.task))
+        .bind(db_dead_letter.error)
+        .bind(db_dead_letter.attempts)
+        .bind(to_millis(db_dead_letter.failed_at))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_dead_letter(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DeadLetter>, crate::error::SchedulerError> {
+        let row = sqlx::query_as::<_, SqliteDeadLetterRow>(
+            "SELECT id, task_id, task, error, attempts, failed_at FROM dead_letters WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        row.map(SqliteDeadLetterRow::into_dead_letter).transpose()
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, crate::error::SchedulerError> {
+        let rows = sqlx::query_as::<_, SqliteDeadLetterRow>(
+            "SELECT id, task_id, task, error, attempts, failed_at FROM dead_letters ORDER BY failed_at",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(SqliteDeadLetterRow::into_dead_letter)
+            .collect()
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), crate::error::SchedulerError> {
+        sqlx::query("DELETE FROM dead_letters WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SchedulerError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::task::default::Task;
+
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TaskSort {
+    #[default]
+    NextRunAsc,
+    NextRunDesc,
+    CreatedAtAsc,
+    CreatedAtDesc,
+}
+
+impl TaskSort {
+    /// The value `task` is sorted by. Ties are broken by the task id.
+    pub fn key(&self, task: &Task) -> DateTime<Utc> {
+        match self {
+            TaskSort::NextRunAsc | TaskSort::NextRunDesc => task.next_run,
+            TaskSort::CreatedAtAsc | TaskSort::CreatedAtDesc => task.created_at,
+        }
+    }
+
+    pub fn is_descending(&self) -> bool {
+        matches!(self, TaskSort::NextRunDesc | TaskSort::CreatedAtDesc)
+    }
+}
+
+/// Points past the last task of a page. Ties on the sort key are broken by the task id,
+/// so every task is returned exactly once while paging, as long as the sort stays the same.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskCursor {
+    key: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl TaskCursor {
+    /// Points past the task with the given sort key, see [`TaskSort::key`], and id.
+    pub fn new(key: DateTime<Utc>, id: Uuid) -> Self {
+        Self { key, id }
+    }
+
+    pub fn key(&self) -> DateTime<Utc> {
+        self.key
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    /// Queries the next page when passed to [`TaskQuery::after`], `None` on the last page.
+    pub next_cursor: Option<TaskCursor>,
+}
+
+/// Filters, sorts and pages tasks through [`crate::storage::base_storage::Storage::query_tasks`].
+/// Filters that are not set match every task.
+#[derive(Clone, Debug)]
+pub struct TaskQuery {
+    pub chat_id: Option<i64>,
+    pub assignee: Option<String>,
+    pub created_by: Option<String>,
+    pub enabled: Option<bool>,
+    /// Inclusive lower bound of `next_run`.
+    pub next_run_from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound of `next_run`.
+    pub next_run_until: Option<DateTime<Utc>>,
+    /// Matches tasks that have all of these tags.
+    pub tags: Vec<String>,
+    pub sort: TaskSort,
+    pub limit: usize,
+    pub cursor: Option<TaskCursor>,
+}
+
+impl Default for TaskQuery {
+    fn default() -> Self {
+        Self {
+            chat_id: None,
+            assignee: None,
+            created_by: None,
+            enabled: None,
+            next_run_from: None,
+            next_run_until: None,
+            tags: Vec::new(),
+            sort: TaskSort::default(),
+            limit: DEFAULT_LIMIT,
+            cursor: None,
+        }
+    }
+}
+
+impl TaskQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chat_id(mut self, chat_id: i64) -> Self {
+        self.chat_id = Some(chat_id);
+        self
+    }
+
+    pub fn with_assignee(mut self, assignee: impl Into<String>) -> Self {
+        self.assignee = Some(assignee.into());
+        self
+    }
+
+    pub fn with_created_by(mut self, created_by: impl Into<String>) -> Self {
+        self.created_by = Some(created_by.into());
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    pub fn with_next_run_between(mut self, from: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        self.next_run_from = Some(from);
+        self.next_run_until = Some(until);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn with_sort(mut self, sort: TaskSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// The most tasks returned per page, at least one. Defaults to 50.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    /// Continues after the page that returned `cursor`.
+    pub fn after(mut self, cursor: TaskCursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Whether `task` passes the filters, not taking the cursor into account.
+    pub fn matches(&self, task: &Task) -> bool {
+        let metadata = &task.metadata;
+
+        self.chat_id
+            .is_none_or(|chat_id| metadata.chat_id == Some(chat_id))
+            && self
+                .assignee
+                .as_ref()
+                .is_none_or(|assignee| metadata.assignee.as_ref() == Some(assignee))
+            && self
+                .created_by
+                .as_ref()
+                .is_none_or(|created_by| metadata.created_by.as_ref() == Some(created_by))
+            && self.enabled.is_none_or(|enabled| task.enabled == enabled)
+            && self.next_run_from.is_none_or(|from| task.next_run >= from)
+            && self
+                .next_run_until
+                .is_none_or(|until| task.next_run < until)
+            && self.tags.iter().all(|tag| metadata.tags.contains(tag))
+    }
+
+    /// Whether `task` comes after the cursor in the query's sort order.
+    pub fn is_after_cursor(&self, task: &Task) -> bool {
+        self.cursor.as_ref().is_none_or(|cursor| {
+            let position = (self.sort.key(task), task.id);
+            let cursor = (cursor.key, cursor.id);
+
+            if self.sort.is_descending() {
+                position < cursor
+            } else {
+                position > cursor
+            }
+        })
+    }
+
+    /// Builds the page from up to `limit + 1` sorted tasks, the extra one telling whether
+    /// there is a next page.
+    pub fn page_from(&self, mut tasks: Vec<Task>) -> TaskPage {
+        let has_more = tasks.len() > self.limit;
+        tasks.truncate(self.limit);
+
+        let next_cursor = has_more
+            .then(|| tasks.last())
+            .flatten()
+            .map(|task| TaskCursor::new(self.sort.key(task), task.id));
+
+        TaskPage { tasks, next_cursor }
+    }
+}
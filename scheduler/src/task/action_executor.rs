@@ -1,17 +1,64 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::{
     error::SchedulerError,
     task::{
         action::{ActionType, TaskAction},
         default::Task,
+        retry_policy::RetryPolicy,
     },
 };
 
+/// Tells the scheduler what to do with a task after its action was executed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    /// The action ran and the task continues with its schedule. The output, if any, is
+    /// recorded with the run.
+    Completed { output: Option<String> },
+    /// The action didn't run and the task moves on to its next occurrence.
+    Skipped,
+    /// The task is disabled and won't run again.
+    Disable,
+    /// The task runs again at the given time instead of its next occurrence.
+    Reschedule { at: DateTime<Utc> },
+}
+
+impl Default for ExecutionOutcome {
+    fn default() -> Self {
+        ExecutionOutcome::Completed { output: None }
+    }
+}
+
 #[async_trait]
 pub trait ActionExecutor: Send + Sync {
     fn supported_actions(&self) -> Vec<ActionType>;
     async fn execute(&self, task: &Task, action: &TaskAction) -> Result<(), SchedulerError>;
+
+    /// Executes the action and tells the scheduler how to continue with the task.
+    /// Executors that only need to report success or failure implement
+    /// [`ActionExecutor::execute`] instead.
+    async fn execute_with_outcome(
+        &self,
+        task: &Task,
+        action: &TaskAction,
+    ) -> Result<ExecutionOutcome, SchedulerError> {
+        self.execute(task, action)
+            .await
+            .map(|_| ExecutionOutcome::default())
+    }
+
+    /// Retry policy for tasks with the supported actions that don't set their own.
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Timeout for tasks with the supported actions that don't set their own.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 pub type BoxedActionExecutor = Box<dyn ActionExecutor>;
@@ -25,4 +72,20 @@ impl ActionExecutor for Box<dyn ActionExecutor> {
     async fn execute(&self, task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
         self.as_ref().execute(task, action).await
     }
+
+    async fn execute_with_outcome(
+        &self,
+        task: &Task,
+        action: &TaskAction,
+    ) -> Result<ExecutionOutcome, SchedulerError> {
+        self.as_ref().execute_with_outcome(task, action).await
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.as_ref().retry_policy()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.as_ref().timeout()
+    }
 }
@@ -1,6 +1,13 @@
+use std::time::Duration;
+
 use crate::{
     error::SchedulerError,
-    task::{action::TaskAction, action_executor::ActionExecutor, default::Task},
+    task::{
+        action::TaskAction,
+        action_executor::{ActionExecutor, ExecutionOutcome},
+        default::Task,
+        retry_policy::RetryPolicy,
+    },
 };
 
 pub struct ActionRegistry {
@@ -25,18 +32,25 @@ impl ActionRegistry {
     }
 
     pub async fn execute(&self, task: &Task) -> Result<(), SchedulerError> {
+        self.execute_with_outcome(task).await.map(|_| ())
+    }
+
+    pub async fn execute_with_outcome(
+        &self,
+        task: &Task,
+    ) -> Result<ExecutionOutcome, SchedulerError> {
         for executor in &self.executors {
             match &task.action {
                 Some(action) => {
                     if self.can_execute(action, &**executor) {
-                        return executor.execute(task, action).await;
+                        return executor.execute_with_outcome(task, action).await;
                     }
                 }
                 None => return Err(SchedulerError::ActionMissing(task.id.to_string())),
             }
         }
 
-        Ok(())
+        Ok(ExecutionOutcome::default())
     }
 
     pub fn has_executor_for(&self, action: &TaskAction) -> bool {
@@ -48,6 +62,25 @@ impl ActionRegistry {
         false
     }
 
+    /// Returns the default retry policy of the executor for `action`, if it has one.
+    pub fn retry_policy_for(&self, action: &TaskAction) -> Option<RetryPolicy> {
+        self.executor_for(action)
+            .and_then(|executor| executor.retry_policy())
+    }
+
+    /// Returns the default timeout of the executor for `action`, if it has one.
+    pub fn timeout_for(&self, action: &TaskAction) -> Option<Duration> {
+        self.executor_for(action)
+            .and_then(|executor| executor.timeout())
+    }
+
+    fn executor_for(&self, action: &TaskAction) -> Option<&dyn ActionExecutor> {
+        self.executors
+            .iter()
+            .map(|executor| &**executor)
+            .find(|executor| self.can_execute(action, *executor))
+    }
+
     fn can_execute(&self, action: &TaskAction, executor: &dyn ActionExecutor) -> bool {
         executor.supported_actions().contains(&action.action_type())
     }
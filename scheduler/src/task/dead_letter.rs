@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::{JsonValue, time::OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    error::SchedulerError,
+    task::default::{Task, from_offset_datetime, to_offset_datetime},
+};
+
+pub struct DeadLetterDb {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub task: JsonValue,
+    pub error: String,
+    pub attempts: i32,
+    pub failed_at: OffsetDateTime,
+}
+
+/// An occurrence of a task that failed on every attempt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    /// The task as it was when its last attempt failed.
+    pub task: Task,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    pub fn new(task: Task, error: &SchedulerError, failed_at: DateTime<Utc>) -> Self {
+        DeadLetter {
+            id: Uuid::new_v4(),
+            attempts: task.retry_count,
+            task,
+            error: error.to_string(),
+            failed_at,
+        }
+    }
+
+    pub fn to_db_dead_letter(&self) -> Result<DeadLetterDb, SchedulerError> {
+        Ok(DeadLetterDb {
+            id: self.id,
+            task_id: self.task.id,
+            task: serde_json::to_value(&self.task)?,
+            error: self.error.clone(),
+            attempts: self.attempts as i32,
+            failed_at: to_offset_datetime(self.failed_at)?,
+        })
+    }
+
+    pub fn from_db_dead_letter(db_dead_letter: DeadLetterDb) -> Result<Self, SchedulerError> {
+        Ok(DeadLetter {
+            id: db_dead_letter.id,
+            task: serde_json::from_value(db_dead_letter.task)?,
+            error: db_dead_letter.error,
+            attempts: db_dead_letter.attempts as u32,
+            failed_at: from_offset_datetime(db_dead_letter.failed_at),
+        })
+    }
+}
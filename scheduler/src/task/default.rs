@@ -1,18 +1,33 @@
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
 use sqlx::types::{JsonValue, time::OffsetDateTime};
 use uuid::Uuid;
 
-use crate::{error::SchedulerError, task::action::TaskAction};
+use crate::{
+    error::SchedulerError,
+    task::{
+        action::TaskAction,
+        local_time::{resolve_local_datetime, to_local_datetime},
+        metadata::TaskMetadata,
+        misfire_policy::MisfirePolicy,
+        retry_policy::RetryPolicy,
+    },
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TaskType {
     Once,
     Range {
         start_date: DateTime<Utc>,
         end_date: DateTime<Utc>,
     },
+    Cron {
+        expression: String,
+    },
 }
 
 impl From<TaskType> for i16 {
@@ -20,10 +35,12 @@ impl From<TaskType> for i16 {
         match value {
             TaskType::Once => 1,
             TaskType::Range { .. } => 2,
+            TaskType::Cron { .. } => 3,
         }
     }
 }
 
+#[derive(sqlx::FromRow)]
 pub struct TaskDb {
     pub id: Uuid,
     pub schedule_type: i16,
@@ -36,18 +53,133 @@ pub struct TaskDb {
     pub action: JsonValue,
     pub start_date: Option<OffsetDateTime>,
     pub end_date: Option<OffsetDateTime>,
+    pub cron_expression: Option<String>,
+    pub timezone: String,
+    pub delay_between_runs: Option<i64>,
+    pub retry_policy: Option<JsonValue>,
+    pub execution_timeout: Option<i64>,
+    pub misfire_policy: JsonValue,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub assignee: Option<String>,
+    pub chat_id: Option<i64>,
+    pub tags: Vec<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    pub version: i64,
+}
+
+/// The columns a schedule is stored in: its type, the range dates and the cron expression.
+pub(crate) type ScheduleColumns = (
+    i16,
+    Option<OffsetDateTime>,
+    Option<OffsetDateTime>,
+    Option<String>,
+);
+
+impl TaskType {
+    /// Checks that the schedule is well-formed, e.g. that a cron expression parses.
+    pub fn validate(&self) -> Result<(), SchedulerError> {
+        match self {
+            TaskType::Once => Ok(()),
+            TaskType::Range {
+                start_date,
+                end_date,
+            } => {
+                if end_date < start_date {
+                    return Err(SchedulerError::InvalidSchedule(
+                        "Range ends before it starts".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            TaskType::Cron { expression } => parse_cron_schedule(expression).map(|_| ()),
+        }
+    }
+
+    pub(crate) fn to_db_columns(&self) -> Result<ScheduleColumns, SchedulerError> {
+        let schedule_type = i16::from(self.clone());
+
+        Ok(match self {
+            TaskType::Once => (schedule_type, None, None, None),
+            TaskType::Range {
+                start_date,
+                end_date,
+            } => (
+                schedule_type,
+                Some(to_offset_datetime(*start_date)?),
+                Some(to_offset_datetime(*end_date)?),
+                None,
+            ),
+            TaskType::Cron { expression } => (schedule_type, None, None, Some(expression.clone())),
+        })
+    }
 }
 
-fn to_offset_datetime(dt: DateTime<Utc>) -> Result<OffsetDateTime, SchedulerError> {
+pub(crate) fn to_offset_datetime(dt: DateTime<Utc>) -> Result<OffsetDateTime, SchedulerError> {
     OffsetDateTime::from_unix_timestamp(dt.timestamp())
         .map_err(|e| SchedulerError::DatabaseError(e.to_string()))
 }
 
-fn from_offset_datetime(odt: OffsetDateTime) -> DateTime<Utc> {
+/// Keeps the sub-second part, unlike [`to_offset_datetime`], for comparing against the
+/// current time.
+pub(crate) fn to_precise_offset_datetime(
+    dt: DateTime<Utc>,
+) -> Result<OffsetDateTime, SchedulerError> {
+    let nanos =
+        i128::from(dt.timestamp()) * 1_000_000_000 + i128::from(dt.timestamp_subsec_nanos());
+    OffsetDateTime::from_unix_timestamp_nanos(nanos)
+        .map_err(|e| SchedulerError::DatabaseError(e.to_string()))
+}
+
+pub(crate) fn from_offset_datetime(odt: OffsetDateTime) -> DateTime<Utc> {
     DateTime::<Utc>::from_timestamp_nanos(odt.unix_timestamp_nanos() as i64)
 }
 
-#[derive(Clone, Debug)]
+fn parse_cron_schedule(expression: &str) -> Result<Schedule, SchedulerError> {
+    Ok(Schedule::from_str(expression)?)
+}
+
+fn parse_timezone(timezone: &str) -> Result<Tz, SchedulerError> {
+    timezone
+        .parse::<Tz>()
+        .map_err(|_| SchedulerError::InvalidTimezone(timezone.to_string()))
+}
+
+// The cron expression is evaluated against the local wall-clock time, so that DST
+// transitions are resolved the same way as for the other schedules.
+fn next_cron_run(
+    schedule: &Schedule,
+    timezone: &Tz,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let local_after = Utc.from_utc_datetime(&to_local_datetime(timezone, after));
+
+    schedule
+        .after(&local_after)
+        .map(|local| resolve_local_datetime(timezone, local.naive_utc()))
+        .find(|next_run| *next_run > after)
+}
+
+// Searches backwards from the local wall-clock time, the same way `next_cron_run` searches
+// forwards.
+fn latest_cron_run(
+    schedule: &Schedule,
+    timezone: &Tz,
+    until: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let local_until =
+        Utc.from_utc_datetime(&to_local_datetime(timezone, until)) + chrono::Duration::seconds(1);
+
+    schedule
+        .after(&local_until)
+        .rev()
+        .map(|local| resolve_local_datetime(timezone, local.naive_utc()))
+        .find(|latest_run| *latest_run <= until)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Task {
     pub id: Uuid,
     pub next_run: DateTime<Utc>,
@@ -59,13 +191,42 @@ pub struct Task {
     pub schedule: TaskType,
     pub action: Option<TaskAction>,
     pub delay_between_runs: Option<chrono::Duration>,
+    pub timezone: Tz,
+    /// When `None`, the default of the task's action executor is used, or else
+    /// [`RetryPolicy::default`].
+    pub retry_policy: Option<RetryPolicy>,
+    /// How long a single attempt may take before it's cancelled and counted as a failure.
+    /// When `None`, the default of the task's action executor is used, if any.
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    pub misfire_policy: MisfirePolicy,
+    #[serde(default)]
+    pub metadata: TaskMetadata,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Set by the storage whenever the task is saved.
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    /// Incremented by the storage on every save, zero for tasks that were never saved.
+    /// Saving a task fails with [`SchedulerError::VersionConflict`] when it was saved by
+    /// someone else since it was read.
+    #[serde(default)]
+    pub version: i64,
 }
 
 impl Default for Task {
     fn default() -> Self {
+        Self::new_at(Utc::now())
+    }
+}
+
+impl Task {
+    /// Creates a task with the default settings that was created and is due at `now`,
+    /// for callers that read the time from a [`Clock`](crate::clock::Clock).
+    pub fn new_at(now: DateTime<Utc>) -> Self {
         Self {
             id: Uuid::new_v4(),
-            next_run: Utc::now(),
+            next_run: now,
             last_run: None,
             enabled: true,
             retry_count: 0,
@@ -74,11 +235,17 @@ impl Default for Task {
             schedule: TaskType::Once,
             action: None,
             delay_between_runs: None,
+            timezone: Tz::UTC,
+            retry_policy: None,
+            timeout: None,
+            misfire_policy: MisfirePolicy::default(),
+            metadata: TaskMetadata::default(),
+            created_at: now,
+            updated_at: now,
+            version: 0,
         }
     }
-}
 
-impl Task {
     pub fn new_with_datetime_range(
         start_date: DateTime<Utc>,
         end_date: DateTime<Utc>,
@@ -104,6 +271,39 @@ impl Task {
         }
     }
 
+    /// Creates a task that runs on every occurrence of a cron expression, evaluated in the
+    /// given timezone. The expression uses the `cron` crate syntax, which starts with a
+    /// seconds field, e.g. `0 30 8 * * Mon-Fri *` for every weekday at 08:30.
+    pub fn new_with_cron(
+        expression: &str,
+        timezone: Tz,
+        action: TaskAction,
+    ) -> Result<Self, SchedulerError> {
+        Self::new_with_cron_at(expression, timezone, action, Utc::now())
+    }
+
+    /// Like [`Task::new_with_cron`], but runs on the first occurrence after `now` instead
+    /// of the current time.
+    pub fn new_with_cron_at(
+        expression: &str,
+        timezone: Tz,
+        action: TaskAction,
+        now: DateTime<Utc>,
+    ) -> Result<Self, SchedulerError> {
+        let next_run = next_cron_run(&parse_cron_schedule(expression)?, &timezone, now)
+            .ok_or(SchedulerError::NoChronoNext)?;
+
+        Ok(Task {
+            schedule: TaskType::Cron {
+                expression: expression.to_string(),
+            },
+            next_run,
+            timezone,
+            action: Some(action),
+            ..Self::new_at(now)
+        })
+    }
+
     pub fn with_max_retries(mut self, max_retries: u32) -> Self {
         self.max_retries = max_retries;
         self
@@ -114,19 +314,119 @@ impl Task {
         self
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_misfire_policy(mut self, misfire_policy: MisfirePolicy) -> Self {
+        self.misfire_policy = misfire_policy;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: TaskMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     pub fn with_delay_between_runs(mut self, delay: chrono::Duration) -> Self {
         self.delay_between_runs = Some(delay);
         self
     }
 
-    pub fn calculate_next_run(&mut self) {
+    /// Sets the IANA timezone in which the schedule is evaluated. Recurrences then keep
+    /// the same local wall-clock time across DST transitions.
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    // Occurrences are counted from `start_date`, so a `next_run` that was moved off them,
+    // e.g. to run the task right away, doesn't shift the ones after it. Delays made of
+    // whole days keep the wall-clock time of `start_date` in the task's timezone, while
+    // shorter delays are added as elapsed time.
+    fn next_range_run(&self, start_date: DateTime<Utc>, delay: chrono::Duration) -> DateTime<Utc> {
+        if delay.num_days() < 1 || delay != chrono::Duration::days(delay.num_days()) {
+            let delay_ms = delay.num_milliseconds();
+            let elapsed_ms = (self.next_run - start_date).num_milliseconds();
+
+            return (delay_ms > 0)
+                .then(|| elapsed_ms.div_euclid(delay_ms) + 1)
+                .and_then(|occurrence| delay_ms.checked_mul(occurrence))
+                .and_then(chrono::Duration::try_milliseconds)
+                .and_then(|offset| start_date.checked_add_signed(offset))
+                .unwrap_or(self.next_run + delay);
+        }
+
+        let start_local = to_local_datetime(&self.timezone, start_date);
+        let current_local = to_local_datetime(&self.timezone, self.next_run);
+        let elapsed_days = (current_local.date() - start_local.date()).num_days();
+        let mut occurrence = elapsed_days.div_euclid(delay.num_days());
+
+        loop {
+            let next_run =
+                resolve_local_datetime(&self.timezone, start_local + delay * occurrence as i32);
+
+            if next_run > self.next_run {
+                return next_run;
+            }
+
+            occurrence += 1;
+        }
+    }
+
+    /// The latest occurrence at or before `until`, counted from `start_date` the same way
+    /// as in [`Task::next_range_run`].
+    fn latest_range_run(
+        &self,
+        start_date: DateTime<Utc>,
+        delay: chrono::Duration,
+        until: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        if until < start_date {
+            return None;
+        }
+
+        if delay.num_days() < 1 || delay != chrono::Duration::days(delay.num_days()) {
+            let delay_ms = delay.num_milliseconds();
+            let elapsed_ms = (until - start_date).num_milliseconds();
+
+            return (delay_ms > 0)
+                .then(|| elapsed_ms / delay_ms)
+                .and_then(|occurrence| delay_ms.checked_mul(occurrence))
+                .and_then(chrono::Duration::try_milliseconds)
+                .and_then(|offset| start_date.checked_add_signed(offset));
+        }
+
+        let start_local = to_local_datetime(&self.timezone, start_date);
+        let until_local = to_local_datetime(&self.timezone, until);
+        let elapsed_days = (until_local.date() - start_local.date()).num_days();
+
+        // Resolving the wall-clock time can move an occurrence past `until` around DST
+        // transitions, in which case the one before it is the latest.
+        (0..=elapsed_days.div_euclid(delay.num_days()))
+            .rev()
+            .map(|occurrence| {
+                resolve_local_datetime(&self.timezone, start_local + delay * occurrence as i32)
+            })
+            .find(|latest_run| *latest_run <= until)
+    }
+
+    pub fn calculate_next_run(&mut self) -> Result<(), SchedulerError> {
         match &self.schedule {
             TaskType::Range {
-                start_date: _,
+                start_date,
                 end_date,
             } => {
-                let next_run =
-                    self.next_run + self.delay_between_runs.unwrap_or(chrono::Duration::days(1));
+                let next_run = self.next_range_run(
+                    *start_date,
+                    self.delay_between_runs.unwrap_or(chrono::Duration::days(1)),
+                );
 
                 if next_run <= *end_date {
                     self.next_run = next_run;
@@ -134,15 +434,61 @@ impl Task {
                     self.enabled = false;
                 }
             }
+            TaskType::Cron { expression } => {
+                let next_run = next_cron_run(
+                    &parse_cron_schedule(expression)?,
+                    &self.timezone,
+                    self.next_run,
+                );
+
+                match next_run {
+                    Some(next_run) => self.next_run = next_run,
+                    None => self.enabled = false,
+                }
+            }
             TaskType::Once => {
                 self.enabled = false;
             }
         }
+
+        Ok(())
+    }
+
+    /// Moves `next_run` to the latest occurrence that is due at `now`, skipping the ones
+    /// before it. Tasks without a later occurrence keep their `next_run`.
+    pub fn skip_to_latest_occurrence(&mut self, now: DateTime<Utc>) -> Result<(), SchedulerError> {
+        let latest_run = match &self.schedule {
+            TaskType::Range {
+                start_date,
+                end_date,
+            } => self.latest_range_run(
+                *start_date,
+                self.delay_between_runs.unwrap_or(chrono::Duration::days(1)),
+                now.min(*end_date),
+            ),
+            TaskType::Cron { expression } => {
+                latest_cron_run(&parse_cron_schedule(expression)?, &self.timezone, now)
+            }
+            TaskType::Once => None,
+        };
+
+        if let Some(latest_run) = latest_run.filter(|latest_run| *latest_run > self.next_run) {
+            self.next_run = latest_run;
+        }
+
+        Ok(())
     }
 
     pub fn calcluate_retry_delay(&self) -> Duration {
-        let multiplier = 2_u64.pow(self.retry_count);
-        Duration::from_millis(self.retry_delay.as_millis() as u64 * multiplier)
+        self.calculate_retry_delay_with(&RetryPolicy::default())
+    }
+
+    /// Uses `default_policy` when the task doesn't have a retry policy of its own.
+    pub fn calculate_retry_delay_with(&self, default_policy: &RetryPolicy) -> Duration {
+        self.retry_policy
+            .as_ref()
+            .unwrap_or(default_policy)
+            .delay(self.retry_delay, self.retry_count)
     }
 
     pub fn should_retry(&self) -> bool {
@@ -154,7 +500,6 @@ impl Task {
     }
 
     pub fn to_db_task(&self) -> Result<TaskDb, SchedulerError> {
-        let schedule = i16::from(self.schedule.clone());
         let retry_delay = self.retry_delay.as_millis() as i32;
         let max_retries = self.max_retries as i32;
         let retry_count = self.retry_count as i32;
@@ -167,18 +512,7 @@ impl Task {
             }
         };
 
-        let (start_date, end_date) = match &self.schedule {
-            TaskType::Range {
-                start_date,
-                end_date,
-            } => {
-                let start_date = to_offset_datetime(*start_date)?;
-                let end_date = to_offset_datetime(*end_date)?;
-
-                (Some(start_date), Some(end_date))
-            }
-            _ => (None, None),
-        };
+        let (schedule, start_date, end_date, cron_expression) = self.schedule.to_db_columns()?;
 
         Ok(TaskDb {
             id: self.id,
@@ -192,6 +526,25 @@ impl Task {
             action: serde_json::to_value(action)?,
             start_date,
             end_date,
+            cron_expression,
+            timezone: self.timezone.name().to_string(),
+            delay_between_runs: self.delay_between_runs.map(|d| d.num_milliseconds()),
+            retry_policy: self
+                .retry_policy
+                .as_ref()
+                .map(serde_json::to_value)
+                .transpose()?,
+            execution_timeout: self.timeout.map(|timeout| timeout.as_millis() as i64),
+            misfire_policy: serde_json::to_value(&self.misfire_policy)?,
+            title: self.metadata.title.clone(),
+            description: self.metadata.description.clone(),
+            created_by: self.metadata.created_by.clone(),
+            assignee: self.metadata.assignee.clone(),
+            chat_id: self.metadata.chat_id,
+            tags: self.metadata.tags.clone(),
+            created_at: to_offset_datetime(self.created_at)?,
+            updated_at: to_offset_datetime(self.updated_at)?,
+            version: self.version,
         })
     }
 
@@ -206,6 +559,13 @@ impl Task {
                     SchedulerError::DatabaseError("Missing end_date for Range task".to_string())
                 })?),
             },
+            3 => TaskType::Cron {
+                expression: db_task.cron_expression.ok_or_else(|| {
+                    SchedulerError::DatabaseError(
+                        "Missing cron_expression for Cron task".to_string(),
+                    )
+                })?,
+            },
             _ => {
                 return Err(SchedulerError::DatabaseError(
                     "Invalid schedule type".to_string(),
@@ -218,6 +578,11 @@ impl Task {
         let next_run = from_offset_datetime(db_task.next_run);
         let last_run = db_task.last_run.map(from_offset_datetime);
         let action: TaskAction = serde_json::from_value(db_task.action)?;
+        let timezone = parse_timezone(&db_task.timezone)?;
+        let retry_policy = db_task
+            .retry_policy
+            .map(serde_json::from_value)
+            .transpose()?;
 
         Ok(Task {
             id: db_task.id,
@@ -229,7 +594,26 @@ impl Task {
             max_retries,
             retry_delay,
             action: Some(action),
-            delay_between_runs: None,
+            delay_between_runs: db_task
+                .delay_between_runs
+                .map(chrono::Duration::milliseconds),
+            timezone,
+            retry_policy,
+            timeout: db_task
+                .execution_timeout
+                .map(|timeout| Duration::from_millis(timeout as u64)),
+            misfire_policy: serde_json::from_value(db_task.misfire_policy)?,
+            metadata: TaskMetadata {
+                title: db_task.title,
+                description: db_task.description,
+                created_by: db_task.created_by,
+                assignee: db_task.assignee,
+                chat_id: db_task.chat_id,
+                tags: db_task.tags,
+            },
+            created_at: from_offset_datetime(db_task.created_at),
+            updated_at: from_offset_datetime(db_task.updated_at),
+            version: db_task.version,
         })
     }
 }
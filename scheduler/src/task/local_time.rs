@@ -0,0 +1,26 @@
+use chrono::{DateTime, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Converts a wall-clock time in `timezone` to a UTC instant.
+///
+/// DST transitions are resolved as follows:
+/// - ambiguous times (clocks going back) resolve to the earlier of the two instants,
+/// - times skipped by clocks going forward are shifted forward by the length of the gap,
+///   so 02:30 on a day where 02:00 jumps to 03:00 resolves to 03:30.
+pub fn resolve_local_datetime(timezone: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match timezone.from_local_datetime(&local) {
+        chrono::LocalResult::Single(dt) => dt.with_timezone(&Utc),
+        chrono::LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        chrono::LocalResult::None => {
+            // Interpret the time with the offset that was in effect before the transition.
+            let offset_before = timezone
+                .offset_from_utc_datetime(&(local - chrono::Duration::days(1)))
+                .fix();
+            Utc.from_utc_datetime(&(local - offset_before))
+        }
+    }
+}
+
+pub fn to_local_datetime(timezone: &Tz, dt: DateTime<Utc>) -> NaiveDateTime {
+    dt.with_timezone(timezone).naive_local()
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Describes a task for the people managing it. The scheduler itself doesn't use any of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Identifies who created the task, e.g. a chat user id.
+    pub created_by: Option<String>,
+    /// Identifies who the task is for, e.g. a user mention.
+    pub assignee: Option<String>,
+    /// The chat the task was created in.
+    pub chat_id: Option<i64>,
+    pub tags: Vec<String>,
+}
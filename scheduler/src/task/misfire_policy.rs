@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Decides what happens with the occurrences a task missed, e.g. while the scheduler was
+/// down. A task misfired when it is claimed later than the scheduler's misfire threshold,
+/// see [`crate::task::task_scheduler::TaskScheduler::with_misfire_threshold`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MisfirePolicy {
+    /// Runs the latest missed occurrence once and continues with the next future one.
+    #[default]
+    FireOnce,
+    /// Skips the missed occurrences and continues with the next future one.
+    Skip,
+    /// Runs every missed occurrence, one after another.
+    FireAll,
+    /// Like [`MisfirePolicy::FireOnce`], but skips the latest missed occurrence when it's
+    /// older than the given duration.
+    DropOlderThan(Duration),
+}
+
+impl MisfirePolicy {
+    /// Whether the latest missed occurrence, which is `age` old, still runs.
+    pub(crate) fn fires(&self, age: Duration) -> bool {
+        match self {
+            MisfirePolicy::FireOnce | MisfirePolicy::FireAll => true,
+            MisfirePolicy::Skip => false,
+            MisfirePolicy::DropOlderThan(max_age) => age <= *max_age,
+        }
+    }
+}
@@ -1,8 +1,14 @@
 pub mod action;
 pub mod action_executor;
 pub mod action_registry;
+pub mod dead_letter;
 pub mod default;
+pub mod local_time;
 pub mod log_executor;
+pub mod metadata;
+pub mod misfire_policy;
+pub mod retry_policy;
+pub mod task_run;
 pub mod task_scheduler;
 
 #[cfg(test)]
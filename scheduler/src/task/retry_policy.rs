@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backoff {
+    /// Waits the base delay before every retry.
+    Fixed,
+    /// Waits the base delay times the retry count.
+    Linear,
+    /// Doubles the delay with every retry.
+    Exponential,
+    /// Waits a random delay between zero and the exponential delay, so that tasks which
+    /// failed together don't retry in lockstep.
+    ExponentialJitter,
+}
+
+/// Decides how long to wait before retrying a failed task, based on the task's
+/// `retry_delay` and its retry count.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub backoff: Backoff,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::exponential()
+    }
+}
+
+impl RetryPolicy {
+    pub fn fixed() -> Self {
+        Self::new(Backoff::Fixed)
+    }
+
+    pub fn linear() -> Self {
+        Self::new(Backoff::Linear)
+    }
+
+    pub fn exponential() -> Self {
+        Self::new(Backoff::Exponential)
+    }
+
+    pub fn exponential_with_jitter() -> Self {
+        Self::new(Backoff::ExponentialJitter)
+    }
+
+    fn new(backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            max_delay: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// Caps the delay between two attempts. Defaults to one hour.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn delay(&self, base_delay: Duration, retry_count: u32) -> Duration {
+        let exponential = || base_delay.saturating_mul(2_u32.saturating_pow(retry_count));
+
+        let delay = match self.backoff {
+            Backoff::Fixed => base_delay,
+            Backoff::Linear => base_delay.saturating_mul(retry_count),
+            Backoff::Exponential => exponential(),
+            Backoff::ExponentialJitter => {
+                let max = exponential().min(self.max_delay);
+                Duration::from_millis(rand::rng().random_range(0..=max.as_millis() as u64))
+            }
+        };
+
+        delay.min(self.max_delay)
+    }
+}
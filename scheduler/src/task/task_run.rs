@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    error::SchedulerError,
+    task::{
+        action_executor::ExecutionOutcome,
+        default::{from_offset_datetime, to_offset_datetime},
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskRunOutcome {
+    Succeeded,
+    Failed,
+    Skipped,
+    TimedOut,
+}
+
+impl From<TaskRunOutcome> for i16 {
+    fn from(value: TaskRunOutcome) -> Self {
+        match value {
+            TaskRunOutcome::Succeeded => 1,
+            TaskRunOutcome::Failed => 2,
+            TaskRunOutcome::Skipped => 3,
+            TaskRunOutcome::TimedOut => 4,
+        }
+    }
+}
+
+impl TryFrom<i16> for TaskRunOutcome {
+    type Error = SchedulerError;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(TaskRunOutcome::Succeeded),
+            2 => Ok(TaskRunOutcome::Failed),
+            3 => Ok(TaskRunOutcome::Skipped),
+            4 => Ok(TaskRunOutcome::TimedOut),
+            _ => Err(SchedulerError::DatabaseError(format!(
+                "Invalid task run outcome: {}",
+                value
+            ))),
+        }
+    }
+}
+
+pub struct TaskRunDb {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub attempt: i32,
+    pub started_at: OffsetDateTime,
+    pub finished_at: OffsetDateTime,
+    pub outcome: i16,
+    pub error: Option<String>,
+    pub output: Option<String>,
+}
+
+/// A single execution attempt of a task.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaskRun {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    /// Starts at 1 and increases with every retry of the same occurrence.
+    pub attempt: u32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: TaskRunOutcome,
+    pub error: Option<String>,
+    /// Output reported by the executor through [`ExecutionOutcome::Completed`].
+    pub output: Option<String>,
+}
+
+impl TaskRun {
+    pub fn new(
+        task_id: Uuid,
+        attempt: u32,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        result: &Result<ExecutionOutcome, SchedulerError>,
+    ) -> Self {
+        let (outcome, error, output) = match result {
+            Ok(ExecutionOutcome::Completed { output }) => {
+                (TaskRunOutcome::Succeeded, None, output.clone())
+            }
+            Ok(ExecutionOutcome::Skipped) => (TaskRunOutcome::Skipped, None, None),
+            Ok(_) => (TaskRunOutcome::Succeeded, None, None),
+            Err(e @ SchedulerError::Timeout(_)) => {
+                (TaskRunOutcome::TimedOut, Some(e.to_string()), None)
+            }
+            Err(e) => (TaskRunOutcome::Failed, Some(e.to_string()), None),
+        };
+
+        TaskRun {
+            id: Uuid::new_v4(),
+            task_id,
+            attempt,
+            started_at,
+            finished_at,
+            outcome,
+            error,
+            output,
+        }
+    }
+
+    pub fn to_db_run(&self) -> Result<TaskRunDb, SchedulerError> {
+        Ok(TaskRunDb {
+            id: self.id,
+            task_id: self.task_id,
+            attempt: self.attempt as i32,
+            started_at: to_offset_datetime(self.started_at)?,
+            finished_at: to_offset_datetime(self.finished_at)?,
+            outcome: i16::from(self.outcome.clone()),
+            error: self.error.clone(),
+            output: self.output.clone(),
+        })
+    }
+
+    pub fn from_db_run(db_run: TaskRunDb) -> Result<Self, SchedulerError> {
+        Ok(TaskRun {
+            id: db_run.id,
+            task_id: db_run.task_id,
+            attempt: db_run.attempt as u32,
+            started_at: from_offset_datetime(db_run.started_at),
+            finished_at: from_offset_datetime(db_run.finished_at),
+            outcome: TaskRunOutcome::try_from(db_run.outcome)?,
+            error: db_run.error,
+            output: db_run.output,
+        })
+    }
+}
@@ -1,20 +1,88 @@
-use std::{collections::HashSet, sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+use std::{
+    any::Any,
+    collections::{HashMap, hash_map::Entry},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    sync::{Notify, OwnedSemaphorePermit, RwLock, Semaphore, watch},
+    task::{self, JoinError, JoinHandle, JoinSet},
+};
 use uuid::Uuid;
 
 use crate::{
-    error::SchedulerError,
-    storage::base_storage::Storage,
-    task::{action_registry::ActionRegistry, default::Task},
+    clock::{Clock, SystemClock, after},
+    error::{ErrorDisposition, SchedulerError},
+    storage::{
+        base_storage::Storage,
+        task_query::{TaskPage, TaskQuery},
+    },
+    task::{
+        action::{ActionType, TaskAction},
+        action_executor::ExecutionOutcome,
+        action_registry::ActionRegistry,
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        misfire_policy::MisfirePolicy,
+        task_run::TaskRun,
+    },
 };
 
+/// Returned by [`TaskScheduler::start`]. Resolves once the scheduler has been stopped and
+/// the tasks it was executing have finished or were handed back to the storage.
+pub struct SchedulerHandle {
+    handle: JoinHandle<()>,
+}
+
+impl Future for SchedulerHandle {
+    type Output = Result<(), SchedulerError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map_err(|e| SchedulerError::TaskExecutionError(e.to_string()))
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
+
+/// How many tasks an instance executes at the same time unless configured otherwise.
+const DEFAULT_MAX_CONCURRENCY: usize = 100;
+
+/// How often saving an executed task is attempted when it keeps being changed concurrently.
+const MAX_SAVE_ATTEMPTS: usize = 3;
+
+type DeadLetterAction = dyn Fn(&DeadLetter) -> TaskAction + Send + Sync;
+
 #[derive(Clone)]
 pub struct TaskScheduler {
     storage: Arc<dyn Storage>,
     action_registry: Arc<ActionRegistry>,
-    running: Arc<RwLock<bool>>,
+    running: Arc<watch::Sender<bool>>,
     check_interval: Duration,
-    executing_tasks: Arc<RwLock<HashSet<Uuid>>>,
+    /// The tasks this instance is executing, as they were when they were claimed.
+    executing_tasks: Arc<RwLock<HashMap<Uuid, Task>>>,
+    worker_id: Arc<str>,
+    lease_duration: Duration,
+    wakeup: Arc<Notify>,
+    shutdown_timeout: Duration,
+    concurrency: Arc<Semaphore>,
+    action_concurrency: Arc<HashMap<ActionType, Arc<Semaphore>>>,
+    batch_size: usize,
+    misfire_threshold: Duration,
+    dead_letter_action: Option<Arc<DeadLetterAction>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl TaskScheduler {
@@ -22,17 +90,104 @@ impl TaskScheduler {
         Self {
             storage,
             action_registry: Arc::new(registry),
-            running: Arc::new(RwLock::new(false)),
-            check_interval: Duration::from_millis(500),
-            executing_tasks: Arc::new(RwLock::new(HashSet::new())),
+            running: Arc::new(watch::Sender::new(false)),
+            check_interval: Duration::from_secs(60),
+            executing_tasks: Arc::new(RwLock::new(HashMap::new())),
+            worker_id: Uuid::new_v4().to_string().into(),
+            lease_duration: Duration::from_secs(300),
+            wakeup: Arc::new(Notify::new()),
+            shutdown_timeout: Duration::from_secs(30),
+            concurrency: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENCY)),
+            action_concurrency: Arc::new(HashMap::new()),
+            batch_size: 100,
+            misfire_threshold: Duration::from_secs(60),
+            dead_letter_action: None,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// The scheduler sleeps until the next task is due, or until it is woken up through
+    /// [`TaskScheduler::wakeup_handle`]. This is the longest it sleeps between two checks
+    /// of the storage regardless.
     pub fn with_check_interval(mut self, interval: Duration) -> Self {
         self.check_interval = interval;
         self
     }
 
+    /// Identifies this scheduler instance when claiming tasks. Defaults to a random id,
+    /// so every replica sharing the same storage gets its own.
+    pub fn with_worker_id(mut self, worker_id: impl Into<String>) -> Self {
+        self.worker_id = worker_id.into().into();
+        self
+    }
+
+    /// How long a claimed task stays leased to this instance. If the instance crashes,
+    /// other instances can claim the task once the lease expires. It is renewed every half
+    /// lease while the task is executing or waiting for a retry.
+    pub fn with_lease_duration(mut self, lease_duration: Duration) -> Self {
+        self.lease_duration = lease_duration;
+        self
+    }
+
+    /// How long stopping the scheduler waits for the tasks it is executing to finish.
+    /// Tasks still running after that are cancelled and released, so they run again on
+    /// the next start or on another instance.
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    /// Limits how many tasks this instance executes at the same time. Ready tasks beyond
+    /// the limit are left in the storage until a running task finishes. Defaults to 100.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.concurrency = Arc::new(Semaphore::new(max_concurrency));
+        self
+    }
+
+    /// Limits how many tasks with the given action type are executed at the same time.
+    /// Claimed tasks wait for their turn while counting towards
+    /// [`TaskScheduler::with_max_concurrency`], renewing their lease so that other
+    /// instances don't claim them in the meantime.
+    pub fn with_action_concurrency(mut self, action_type: ActionType, limit: usize) -> Self {
+        Arc::make_mut(&mut self.action_concurrency)
+            .insert(action_type, Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// The most tasks claimed from the storage at once.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// How late a task may be claimed before its misfire policy applies. Defaults to one
+    /// minute.
+    pub fn with_misfire_threshold(mut self, misfire_threshold: Duration) -> Self {
+        self.misfire_threshold = misfire_threshold;
+        self
+    }
+
+    /// Executes the action built by `action` whenever a task is dead-lettered, e.g. to
+    /// notify an administrator. It is executed once, without retries.
+    pub fn with_dead_letter_action(
+        mut self,
+        action: impl Fn(&DeadLetter) -> TaskAction + Send + Sync + 'static,
+    ) -> Self {
+        self.dead_letter_action = Some(Arc::new(action));
+        self
+    }
+
+    /// The clock that decides when tasks are due and how long retry delays and timeouts
+    /// last. Defaults to the system time. The storage should use the same clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
     pub async fn add_task(&self, task: Task) -> Result<Uuid, SchedulerError> {
         let action = match &task.action {
             Some(act) => act,
@@ -46,30 +201,521 @@ impl TaskScheduler {
         }
 
         self.storage.save_task(task.clone()).await?;
+        self.wakeup.notify_one();
         Ok(task.id)
     }
 
-    async fn execute_task_with_retry(
-        registry: Arc<ActionRegistry>,
-        mut task: Task,
-        storage: Arc<dyn Storage>,
-        executing_tasks: Arc<RwLock<HashSet<Uuid>>>,
-    ) {
-        loop {
-            match registry.execute(&task).await {
-                Ok(_) => {
-                    log::info!("Task {} executed successfully", task.id);
-                    task.reset_retry_count();
-                    task.last_run = Some(chrono::Utc::now());
+    pub async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage, SchedulerError> {
+        self.storage.query_tasks(query).await
+    }
 
-                    let mut executing_guard = executing_tasks.write().await;
-                    executing_guard.remove(&task.id);
+    /// Returns the occurrences of tasks that failed on every attempt, oldest first.
+    pub async fn list_dead_letters(&self) -> Result<Vec<DeadLetter>, SchedulerError> {
+        self.storage.get_dead_letters().await
+    }
 
-                    task.calculate_next_run();
+    /// Runs a dead-lettered occurrence again as a new one-off task and removes the dead
+    /// letter. Returns the id of the new task.
+    pub async fn retry_dead_letter(&self, id: Uuid) -> Result<Uuid, SchedulerError> {
+        let dead_letter = self
+            .storage
+            .get_dead_letter(id)
+            .await?
+            .ok_or_else(|| SchedulerError::DeadLetterNotFound(id.to_string()))?;
 
-                    if let Err(e) = storage.save_task(task).await {
-                        log::error!("Error updating task {:?}", e);
-                    }
+        // Deleted first, so that the occurrence can't be retried twice if deleting fails,
+        // and restored if the task can't be added.
+        self.storage.delete_dead_letter(id).await?;
+
+        let now = self.clock.now();
+        let added = self
+            .add_task(Task {
+                id: Uuid::new_v4(),
+                schedule: TaskType::Once,
+                next_run: now,
+                enabled: true,
+                retry_count: 0,
+                created_at: now,
+                version: 0,
+                ..dead_letter.task.clone()
+            })
+            .await;
+
+        if added.is_err()
+            && let Err(e) = self.storage.save_dead_letter(dead_letter).await
+        {
+            log::error!("Error restoring dead letter {}: {:?}", id, e);
+        }
+        added
+    }
+
+    pub async fn discard_dead_letter(&self, id: Uuid) -> Result<(), SchedulerError> {
+        if self.storage.get_dead_letter(id).await?.is_none() {
+            return Err(SchedulerError::DeadLetterNotFound(id.to_string()));
+        }
+
+        self.storage.delete_dead_letter(id).await
+    }
+
+    /// Stops a task from running until it's resumed.
+    pub async fn pause_task(&self, id: Uuid) -> Result<(), SchedulerError> {
+        if !self.find_task(id).await?.enabled {
+            return Err(SchedulerError::InvalidTaskState(format!(
+                "Task {} is already paused",
+                id
+            )));
+        }
+
+        self.storage.set_task_enabled(id, false).await?;
+        self.wakeup.notify_one();
+        Ok(())
+    }
+
+    /// Resumes a paused task. Occurrences it missed while paused are handled by its
+    /// misfire policy.
+    pub async fn resume_task(&self, id: Uuid) -> Result<(), SchedulerError> {
+        if self.find_task(id).await?.enabled {
+            return Err(SchedulerError::InvalidTaskState(format!(
+                "Task {} is not paused",
+                id
+            )));
+        }
+
+        self.storage.set_task_enabled(id, true).await?;
+        self.wakeup.notify_one();
+        Ok(())
+    }
+
+    /// Deletes a task. If it's executing, the current attempt finishes but isn't retried.
+    pub async fn cancel_task(&self, id: Uuid) -> Result<(), SchedulerError> {
+        self.find_task(id).await?;
+
+        self.storage.delete_task(id).await?;
+        self.wakeup.notify_one();
+        Ok(())
+    }
+
+    /// Replaces the schedule of a task, which next runs at `next_run`.
+    pub async fn reschedule_task(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: DateTime<Utc>,
+    ) -> Result<(), SchedulerError> {
+        schedule.validate()?;
+        if let TaskType::Range {
+            start_date,
+            end_date,
+        } = &schedule
+            && (next_run < *start_date || next_run > *end_date)
+        {
+            return Err(SchedulerError::InvalidSchedule(
+                "The next run is outside of the range".to_string(),
+            ));
+        }
+        self.find_task(id).await?;
+
+        self.storage
+            .set_task_schedule(id, schedule, next_run)
+            .await?;
+        self.wakeup.notify_one();
+        Ok(())
+    }
+
+    /// Runs a task right away. Its schedule continues as before afterwards.
+    pub async fn run_now(&self, id: Uuid) -> Result<(), SchedulerError> {
+        if !self.find_task(id).await?.enabled {
+            return Err(SchedulerError::InvalidTaskState(format!(
+                "Task {} is paused",
+                id
+            )));
+        }
+
+        self.storage.set_task_next_run(id, self.clock.now()).await?;
+        self.wakeup.notify_one();
+        Ok(())
+    }
+
+    async fn find_task(&self, id: Uuid) -> Result<Task, SchedulerError> {
+        self.storage
+            .get_task(id)
+            .await?
+            .ok_or_else(|| SchedulerError::TaskNotFound(id.to_string()))
+    }
+
+    /// Whether the task was paused or deleted since it was claimed.
+    async fn is_interrupted(&self, task_id: Uuid) -> bool {
+        matches!(
+            self.storage.get_task(task_id).await,
+            Ok(None) | Ok(Some(Task { enabled: false, .. }))
+        )
+    }
+
+    /// Returns a handle that wakes the scheduler up so it checks the storage right away.
+    /// Use it after changing tasks in the storage directly instead of via the scheduler.
+    pub fn wakeup_handle(&self) -> Arc<Notify> {
+        Arc::clone(&self.wakeup)
+    }
+
+    /// When the storage should be checked again: once the next task is due, but no later
+    /// than the check interval from now.
+    async fn next_check_time(&self) -> DateTime<Utc> {
+        let latest = after(self.clock.now(), self.check_interval);
+
+        match self.storage.next_due_time().await {
+            Ok(Some(next_due_time)) => next_due_time.min(latest),
+            Ok(None) => latest,
+            Err(e) => {
+                log::error!("Error fetching next due time: {:?}", e);
+                latest
+            }
+        }
+    }
+
+    fn schedule_next_run(task: &mut Task) {
+        if let Err(e) = task.calculate_next_run() {
+            log::error!(
+                "Error calculating next run for task {}: {:?}. Disabling it.",
+                task.id,
+                e
+            );
+            task.enabled = false;
+        }
+    }
+
+    /// Applies the misfire policy of a task that was claimed too late, e.g. after the
+    /// scheduler was down. Lateness is measured at `claimed_at`, so that waiting for an
+    /// action permit doesn't count. Returns whether the task should still be executed.
+    async fn handle_misfire(&self, task: &mut Task, claimed_at: DateTime<Utc>) -> bool {
+        let overdue = (claimed_at - task.next_run).to_std().unwrap_or_default();
+
+        if overdue <= self.misfire_threshold || task.misfire_policy == MisfirePolicy::FireAll {
+            return true;
+        }
+
+        if let Err(e) = task.skip_to_latest_occurrence(claimed_at) {
+            log::error!(
+                "Error skipping missed runs of task {}: {:?}. Disabling it.",
+                task.id,
+                e
+            );
+            task.enabled = false;
+            return false;
+        }
+
+        let age = (claimed_at - task.next_run).to_std().unwrap_or_default();
+        if task.misfire_policy.fires(age) {
+            log::info!(
+                "Task {} misfired by {:?}, running it once",
+                task.id,
+                overdue
+            );
+            return true;
+        }
+
+        log::info!("Task {} misfired by {:?}, skipping it", task.id, overdue);
+        let now = self.clock.now();
+        let outcome = Ok(ExecutionOutcome::Skipped);
+        self.record_task_run(TaskRun::new(
+            task.id,
+            task.retry_count + 1,
+            now,
+            now,
+            &outcome,
+        ))
+        .await;
+        Self::apply_outcome(task, ExecutionOutcome::Skipped, now);
+        false
+    }
+
+    async fn execute_action(&self, task: &Task) -> Result<ExecutionOutcome, SchedulerError> {
+        let timeout = task.timeout.or_else(|| {
+            task.action
+                .as_ref()
+                .and_then(|action| self.action_registry.timeout_for(action))
+        });
+
+        let deadline = timeout.map(|timeout| (timeout, after(self.clock.now(), timeout)));
+
+        // The executor runs on its own task so that a panic only fails this attempt, and
+        // the set aborts it when dropped, e.g. on timeout or when the scheduler shuts down.
+        let mut execution = JoinSet::new();
+        let registry = Arc::clone(&self.action_registry);
+        let executed_task = task.clone();
+        execution.spawn(async move { registry.execute_with_outcome(&executed_task).await });
+
+        let timed_out = async {
+            match deadline {
+                Some((timeout, deadline)) => {
+                    self.clock.sleep_until(deadline).await;
+                    timeout
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(timed_out);
+
+        // The lease is renewed every half lease, so that an execution taking longer than
+        // the lease isn't claimed by another instance meanwhile.
+        let joined = loop {
+            let renew_at = after(self.clock.now(), self.lease_duration / 2);
+            tokio::select! {
+                biased;
+                joined = execution.join_next() => break joined,
+                timeout = &mut timed_out => {
+                    log::warn!("Task {} timed out after {:?}", task.id, timeout);
+                    return Err(SchedulerError::Timeout(timeout));
+                }
+                _ = self.clock.sleep_until(renew_at) => {}
+            }
+            self.renew_lease(task.id, self.lease_duration).await;
+        };
+
+        match joined {
+            Some(Ok(result)) => result,
+            Some(Err(e)) if e.is_panic() => {
+                let message = panic_message(e.into_panic());
+                log::error!(
+                    "Executor panicked while executing task {}: {}",
+                    task.id,
+                    message
+                );
+                Err(SchedulerError::ExecutorPanicked(message))
+            }
+            Some(Err(e)) => Err(SchedulerError::TaskExecutionError(e.to_string())),
+            None => Err(SchedulerError::TaskExecutionError(
+                "Task execution was not started".to_string(),
+            )),
+        }
+    }
+
+    fn apply_outcome(task: &mut Task, outcome: ExecutionOutcome, now: DateTime<Utc>) {
+        match outcome {
+            ExecutionOutcome::Completed { .. } => {
+                log::info!("Task {} executed successfully", task.id);
+                task.last_run = Some(now);
+                Self::schedule_next_run(task);
+            }
+            ExecutionOutcome::Skipped => {
+                log::info!("Task {} skipped its occurrence", task.id);
+                Self::schedule_next_run(task);
+            }
+            ExecutionOutcome::Disable => {
+                log::info!("Task {} was disabled by its executor", task.id);
+                task.enabled = false;
+            }
+            ExecutionOutcome::Reschedule { at } => {
+                log::info!("Task {} was rescheduled to {}", task.id, at);
+                task.next_run = at;
+            }
+        }
+    }
+
+    async fn record_task_run(&self, run: TaskRun) {
+        let task_id = run.task_id;
+
+        if let Err(e) = self.storage.record_task_run(run).await {
+            log::error!("Error recording run of task {}: {:?}", task_id, e);
+        }
+    }
+
+    async fn dead_letter_task(&self, task: &Task, error: &SchedulerError) {
+        let dead_letter = DeadLetter::new(task.clone(), error, self.clock.now());
+        let notification = self
+            .dead_letter_action
+            .as_ref()
+            .map(|action| Task::new_with_datetime(dead_letter.failed_at, action(&dead_letter)));
+
+        if let Err(e) = self.storage.save_dead_letter(dead_letter).await {
+            log::error!("Error dead-lettering task {}: {:?}", task.id, e);
+        }
+
+        // Executed like any task, so that a hanging or panicking notification can't keep
+        // the dead-lettered task from being saved and released.
+        if let Some(notification) = notification
+            && let Err(e) = self.execute_action(&notification).await
+        {
+            log::error!(
+                "Error notifying about dead-lettered task {}: {:?}",
+                task.id,
+                e
+            );
+        }
+    }
+
+    async fn release_task(&self, task_id: Uuid) {
+        self.executing_tasks.write().await.remove(&task_id);
+
+        if let Err(e) = self.storage.release_task(task_id, &self.worker_id).await {
+            log::error!("Error releasing task {}: {:?}", task_id, e);
+        }
+
+        self.wakeup.notify_one();
+    }
+
+    async fn renew_lease(&self, task_id: Uuid, lease: Duration) {
+        if let Err(e) = self
+            .storage
+            .renew_lease(task_id, &self.worker_id, lease)
+            .await
+        {
+            log::error!("Error renewing the lease of task {}: {:?}", task_id, e);
+        }
+    }
+
+    /// Starts from the stored task, so that every change made while the task was executing
+    /// is kept, and applies the changes of the execution to the fields nobody else changed.
+    fn merge_executed_task(claimed: &Task, executed: Task, stored: Task) -> Task {
+        let mut merged = stored;
+
+        if merged.next_run == claimed.next_run && merged.schedule == claimed.schedule {
+            merged.next_run = executed.next_run;
+        }
+        if merged.enabled == claimed.enabled {
+            merged.enabled = executed.enabled;
+        }
+        if merged.last_run == claimed.last_run {
+            merged.last_run = executed.last_run;
+        }
+        if merged.retry_count == claimed.retry_count {
+            merged.retry_count = executed.retry_count;
+        }
+
+        merged
+    }
+
+    async fn save_executed_task(&self, mut claimed: Task, mut task: Task) {
+        for _ in 0..MAX_SAVE_ATTEMPTS {
+            match self.storage.save_task(task.clone()).await {
+                Ok(_) => return,
+                Err(SchedulerError::VersionConflict(_)) => {}
+                Err(e) => {
+                    log::error!("Error updating task {:?}", e);
+                    return;
+                }
+            }
+
+            match self.storage.get_task(task.id).await {
+                Ok(Some(stored)) => {
+                    log::info!(
+                        "Task {} was changed while executing, merging the changes",
+                        task.id
+                    );
+                    task = Self::merge_executed_task(&claimed, task, stored.clone());
+                    claimed = stored;
+                }
+                Ok(None) => {
+                    log::info!("Task {} was deleted while executing", task.id);
+                    return;
+                }
+                Err(e) => {
+                    log::error!("Error reading task {} to merge it: {:?}", task.id, e);
+                    return;
+                }
+            }
+        }
+
+        log::error!(
+            "Task {} kept changing while it was saved, giving up after {} attempts",
+            task.id,
+            MAX_SAVE_ATTEMPTS
+        );
+    }
+
+    async fn save_and_release_task(&self, task: Task) {
+        let task_id = task.id;
+        let claimed = self
+            .executing_tasks
+            .read()
+            .await
+            .get(&task_id)
+            .cloned()
+            .unwrap_or_else(|| task.clone());
+
+        self.save_executed_task(claimed, task).await;
+        self.release_task(task_id).await;
+    }
+
+    async fn execute_task(self, mut task: Task, _permit: OwnedSemaphorePermit) {
+        // Executions are spawned right after claiming their task.
+        let claimed_at = self.clock.now();
+        let action_limit = task
+            .action
+            .as_ref()
+            .and_then(|action| self.action_concurrency.get(&action.action_type()))
+            .cloned();
+
+        let _action_permit = match action_limit {
+            Some(limit) => match self.acquire_action_permit(&task, limit).await {
+                Some(permit) => Some(permit),
+                None => {
+                    self.release_task(task.id).await;
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        if !self.handle_misfire(&mut task, claimed_at).await {
+            self.save_and_release_task(task).await;
+            return;
+        }
+
+        self.execute_task_with_retry(task).await;
+    }
+
+    /// Waits for a permit of the task's action type, renewing the task's lease every half
+    /// lease meanwhile. Returns `None` if the scheduler is stopped first.
+    async fn acquire_action_permit(
+        &self,
+        task: &Task,
+        limit: Arc<Semaphore>,
+    ) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = limit.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+
+        let mut running = self.running.subscribe();
+        let stopped = running.wait_for(|running| !*running);
+        let acquire = limit.acquire_owned();
+        tokio::pin!(stopped, acquire);
+
+        let permit = loop {
+            let renew_at = after(self.clock.now(), self.lease_duration / 2);
+            tokio::select! {
+                permit = &mut acquire => break permit.ok(),
+                _ = &mut stopped => return None,
+                _ = self.clock.sleep_until(renew_at) => {}
+            }
+            self.renew_lease(task.id, self.lease_duration).await;
+        };
+
+        // The execution gets a whole lease, just like a task that didn't wait.
+        self.renew_lease(task.id, self.lease_duration).await;
+        permit
+    }
+
+    async fn execute_task_with_retry(&self, mut task: Task) {
+        let mut running = self.running.subscribe();
+
+        loop {
+            let started_at = self.clock.now();
+            let result = self.execute_action(&task).await;
+            let finished_at = self.clock.now();
+            self.record_task_run(TaskRun::new(
+                task.id,
+                task.retry_count + 1,
+                started_at,
+                finished_at,
+                &result,
+            ))
+            .await;
+
+            match result {
+                Ok(outcome) => {
+                    task.reset_retry_count();
+                    Self::apply_outcome(&mut task, outcome, finished_at);
+                    self.save_and_release_task(task).await;
 
                     return;
                 }
@@ -82,19 +728,68 @@ impl TaskScheduler {
                         task.retry_count
                     );
 
-                    if task.should_retry() {
-                        let retry_delay = task.calcluate_retry_delay();
-                        tokio::time::sleep(retry_delay).await;
-                        continue;
+                    let retry_delay = match e.disposition() {
+                        ErrorDisposition::Permanent => None,
+                        ErrorDisposition::RetryAfter(delay) => task.should_retry().then_some(delay),
+                        ErrorDisposition::Retryable => task.should_retry().then(|| {
+                            let default_policy = task
+                                .action
+                                .as_ref()
+                                .and_then(|action| self.action_registry.retry_policy_for(action))
+                                .unwrap_or_default();
+                            task.calculate_retry_delay_with(&default_policy)
+                        }),
+                    };
+
+                    if let Some(retry_delay) = retry_delay {
+                        // The next attempt gets a whole lease once the delay is over, just
+                        // like the first one.
+                        self.renew_lease(task.id, retry_delay.saturating_add(self.lease_duration))
+                            .await;
+
+                        // Waiting from when the attempt finished, rather than from now, keeps
+                        // the retry on time however long recording the run took.
+                        let stopping = tokio::select! {
+                            _ = self.clock.sleep_until(after(finished_at, retry_delay)) => false,
+                            _ = running.wait_for(|running| !*running) => true,
+                        };
+
+                        if self.is_interrupted(task.id).await {
+                            log::info!("Task {} was paused or cancelled, not retrying it", task.id);
+                            task.reset_retry_count();
+                            self.save_and_release_task(task).await;
+                            return;
+                        }
+
+                        if stopping {
+                            // The task is still due, so saving the retry count is enough for
+                            // the retries to carry on after the next start.
+                            log::info!(
+                                "Scheduler is stopping, task {} will be retried on the next start",
+                                task.id
+                            );
+                            self.save_and_release_task(task).await;
+                            return;
+                        }
                     } else {
-                        log::error!("Max retries reached for task {}. Giving up.", task.id);
-                        task.last_run = Some(chrono::Utc::now());
-                        task.calculate_next_run();
+                        if e.disposition() == ErrorDisposition::Permanent {
+                            log::error!(
+                                "Task {} failed permanently. Moving it to dead letters.",
+                                task.id
+                            );
+                        } else {
+                            log::error!(
+                                "Max retries reached for task {}. Moving it to dead letters.",
+                                task.id
+                            );
+                        }
+                        self.dead_letter_task(&task, &e).await;
+
+                        task.last_run = Some(self.clock.now());
+                        Self::schedule_next_run(&mut task);
                         task.reset_retry_count();
 
-                        if let Err(e) = storage.save_task(task).await {
-                            log::error!("Error updating task {:?}", e);
-                        }
+                        self.save_and_release_task(task).await;
                         return;
                     }
                 }
@@ -102,60 +797,43 @@ impl TaskScheduler {
         }
     }
 
-    pub async fn start(&self) -> Result<(), SchedulerError> {
-        {
-            let mut running = self.running.write().await;
-            if *running {
-                return Err(SchedulerError::AlreadyRunning);
-            }
-            *running = true;
-        }
-
-        let storage = Arc::clone(&self.storage);
-        let running = Arc::clone(&self.running);
-        let check_interval = self.check_interval;
-        let executing_tasks = Arc::clone(&self.executing_tasks);
-        let registry = Arc::clone(&self.action_registry);
+    async fn run(self, watcher: Option<JoinHandle<()>>) {
+        let mut running = self.running.subscribe();
+        let mut executions = JoinSet::new();
+        let mut spawned = HashMap::new();
 
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(check_interval);
+        while *running.borrow_and_update() {
+            while let Some(result) = executions.try_join_next_with_id() {
+                self.finish_execution(result, &mut spawned).await;
+            }
 
-            loop {
-                interval.tick().await;
+            let capacity = self.concurrency.available_permits().min(self.batch_size);
 
+            if capacity > 0 {
+                match self
+                    .storage
+                    .claim_ready_tasks(&self.worker_id, self.lease_duration, capacity)
+                    .await
                 {
-                    let should_run = *running.read().await;
-                    if !should_run {
-                        break;
-                    }
-                }
-
-                match storage.get_ready_tasks().await {
                     Ok(ready_tasks) => {
                         for task in ready_tasks {
-                            let mut executing_guard = executing_tasks.write().await;
+                            match self.executing_tasks.write().await.entry(task.id) {
+                                Entry::Occupied(_) => continue,
+                                Entry::Vacant(entry) => {
+                                    entry.insert(task.clone());
+                                }
+                            }
 
-                            if executing_guard.contains(&task.id) {
+                            let Ok(permit) = Arc::clone(&self.concurrency).try_acquire_owned()
+                            else {
+                                self.release_task(task.id).await;
                                 continue;
-                            } else {
-                                executing_guard.insert(task.id);
-                            }
+                            };
 
-                            drop(executing_guard);
-
-                            let storage_clone = Arc::clone(&storage);
-                            let executing_tasks = Arc::clone(&executing_tasks);
-                            let registry = Arc::clone(&registry);
-
-                            tokio::spawn(async move {
-                                Self::execute_task_with_retry(
-                                    registry,
-                                    task,
-                                    storage_clone,
-                                    executing_tasks,
-                                )
-                                .await;
-                            });
+                            let task_id = task.id;
+                            let execution =
+                                executions.spawn(self.clone().execute_task(task, permit));
+                            spawned.insert(execution.id(), task_id);
                         }
                     }
                     Err(e) => {
@@ -163,17 +841,125 @@ impl TaskScheduler {
                     }
                 }
             }
-        });
 
-        Ok(())
+            // Tasks that are already due can't be claimed while all the permits are taken,
+            // so wait for one to be returned instead.
+            let at_capacity = self.concurrency.available_permits() == 0;
+            let next_check = if at_capacity {
+                after(self.clock.now(), self.check_interval)
+            } else {
+                self.next_check_time().await
+            };
+
+            tokio::select! {
+                _ = self.clock.sleep_until(next_check) => {}
+                _ = self.wakeup.notified() => {}
+                _ = running.changed() => {}
+                _ = self.concurrency.acquire(), if at_capacity => {}
+            }
+        }
+
+        // Changes don't matter to a stopped scheduler, and the next start watches again.
+        if let Some(watcher) = watcher {
+            watcher.abort();
+            let _ = watcher.await;
+        }
+
+        self.drain_executions(executions, spawned).await;
+    }
+
+    /// Executions clean up after themselves, unless they panicked outside of the executor.
+    async fn finish_execution(
+        &self,
+        result: Result<(task::Id, ()), JoinError>,
+        spawned: &mut HashMap<task::Id, Uuid>,
+    ) {
+        match result {
+            Ok((id, ())) => {
+                spawned.remove(&id);
+            }
+            Err(e) => {
+                log::error!("Task execution failed: {:?}", e);
+                if let Some(task_id) = spawned.remove(&e.id()) {
+                    self.release_task(task_id).await;
+                }
+            }
+        }
+    }
+
+    async fn drain_executions(
+        &self,
+        mut executions: JoinSet<()>,
+        mut spawned: HashMap<task::Id, Uuid>,
+    ) {
+        if executions.is_empty() {
+            return;
+        }
+
+        log::info!(
+            "Waiting up to {:?} for {} running tasks to finish...",
+            self.shutdown_timeout,
+            executions.len()
+        );
+
+        let deadline = after(self.clock.now(), self.shutdown_timeout);
+        let drained = tokio::select! {
+            biased;
+            _ = async {
+                while let Some(result) = executions.join_next_with_id().await {
+                    self.finish_execution(result, &mut spawned).await;
+                }
+            } => true,
+            _ = self.clock.sleep_until(deadline) => false,
+        };
+
+        if !drained {
+            log::warn!(
+                "{} tasks did not finish within {:?}, cancelling them",
+                executions.len(),
+                self.shutdown_timeout
+            );
+            executions.shutdown().await;
+
+            // Their stored state is the one from before they were claimed, so releasing
+            // them is enough for them to run again.
+            let unfinished: Vec<Uuid> = self.executing_tasks.read().await.keys().copied().collect();
+            for task_id in unfinished {
+                self.release_task(task_id).await;
+            }
+        }
+    }
+
+    /// Starts claiming and executing due tasks in the background. Await the returned
+    /// handle after [`TaskScheduler::stop`] to wait for the running tasks to be drained.
+    pub async fn start(&self) -> Result<SchedulerHandle, SchedulerError> {
+        if self.running.send_replace(true) {
+            return Err(SchedulerError::AlreadyRunning);
+        }
+
+        let watcher = match self.storage.watch_changes(Arc::clone(&self.wakeup)).await {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log::error!(
+                    "Error watching task changes, falling back to checking every {:?}: {:?}",
+                    self.check_interval,
+                    e
+                );
+                None
+            }
+        };
+
+        Ok(SchedulerHandle {
+            handle: tokio::spawn(self.clone().run(watcher)),
+        })
     }
 
+    /// Stops claiming new tasks. The tasks that are already running are drained in the
+    /// background, see [`TaskScheduler::with_shutdown_timeout`].
     pub async fn stop(&self) -> Result<(), SchedulerError> {
-        let mut running = self.running.write().await;
-        if !*running {
+        if !self.running.send_replace(false) {
             return Err(SchedulerError::NotRunning);
         }
-        *running = false;
         Ok(())
     }
 
@@ -182,8 +968,7 @@ impl TaskScheduler {
         tokio::spawn(async move {
             tokio::signal::ctrl_c().await?;
             log::info!("Ctrl-C received, shutting down scheduler...");
-            let mut running_guard = running.write().await;
-            *running_guard = false;
+            running.send_replace(false);
             Ok(())
         })
     }
@@ -1,28 +1,46 @@
 use std::{
-    sync::Arc,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
     time::{self, Duration},
 };
 
 use crate::{
+    clock::{Clock, ManualClock, SystemClock, after},
     db::migrator::Migrator,
-    error::SchedulerError,
+    error::{ErrorDisposition, SchedulerError},
     storage::{
-        base_storage::Storage, database_storage::DatabaseStorage,
+        base_storage::Storage,
+        database_storage::DatabaseStorage,
+        file_storage::FileStorage,
         in_memory_storage::InMemoryStorage,
+        redis_storage::RedisStorage,
+        sqlite_storage::SqliteStorage,
+        task_query::{TaskPage, TaskQuery},
     },
     task::{
         action::{ActionType, TaskAction},
-        action_executor::ActionExecutor,
+        action_executor::{ActionExecutor, ExecutionOutcome},
         action_registry::ActionRegistry,
+        dead_letter::DeadLetter,
         default::{Task, TaskType},
         log_executor::LogExecutor,
+        metadata::TaskMetadata,
+        misfire_policy::MisfirePolicy,
+        retry_policy::RetryPolicy,
+        task_run::{TaskRun, TaskRunOutcome},
         task_scheduler::TaskScheduler,
     },
 };
 use async_trait::async_trait;
+use tempfile::TempDir;
 use testcontainers::runners::AsyncRunner;
 use testcontainers::{ContainerAsync, ImageExt};
 use testcontainers_modules::postgres::Postgres as PostgresImage;
+use testcontainers_modules::redis::{REDIS_PORT, Redis as RedisImage};
 
 /// Test executor that tracks execution attempts
 struct FailCountingExecutor {
@@ -107,7 +125,10 @@ async fn setup_database() -> (sqlx::Pool<sqlx::Postgres>, ContainerAsync<Postgre
     (pool, pg_container)
 }
 
-async fn setup_db_storage(container: &ContainerAsync<PostgresImage>) -> Arc<DatabaseStorage> {
+async fn setup_db_storage(
+    container: &ContainerAsync<PostgresImage>,
+    clock: Arc<dyn Clock>,
+) -> Arc<DatabaseStorage> {
     let port = container
         .get_host_port_ipv4(5432)
         .await
@@ -121,10 +142,114 @@ async fn setup_db_storage(container: &ContainerAsync<PostgresImage>) -> Arc<Data
     Arc::new(
         DatabaseStorage::new(&database_url)
             .await
-            .expect("Failed to create DatabaseStorage"),
+            .expect("Failed to create DatabaseStorage")
+            .with_clock(clock),
+    )
+}
+
+/// Every test gets its own database file, an in-memory database would be empty on every
+/// connection of the pool. It's deleted with `temp_dir`.
+fn sqlite_database_url(temp_dir: &TempDir) -> String {
+    let path = temp_dir
+        .path()
+        .join(format!("scheduler-test-{}.db", uuid::Uuid::new_v4()));
+    format!("sqlite://{}", path.display())
+}
+
+async fn setup_sqlite_storage(database_url: &str, clock: Arc<dyn Clock>) -> Arc<SqliteStorage> {
+    Migrator::run(database_url)
+        .await
+        .expect("Failed to run SQLite migrations");
+
+    Arc::new(
+        SqliteStorage::new(database_url)
+            .await
+            .expect("Failed to create SqliteStorage")
+            .with_clock(clock),
+    )
+}
+
+async fn setup_redis() -> ContainerAsync<RedisImage> {
+    RedisImage::default()
+        .start()
+        .await
+        .expect("Failed to start Redis container")
+}
+
+async fn redis_url(container: &ContainerAsync<RedisImage>) -> String {
+    let port = container
+        .get_host_port_ipv4(REDIS_PORT)
+        .await
+        .expect("Failed to get host port");
+
+    format!("redis://localhost:{}", port)
+}
+
+async fn setup_redis_storage(
+    container: &ContainerAsync<RedisImage>,
+    clock: Arc<dyn Clock>,
+) -> Arc<RedisStorage> {
+    Arc::new(
+        RedisStorage::new(&redis_url(container).await)
+            .await
+            .expect("Failed to create RedisStorage")
+            .with_clock(clock),
+    )
+}
+
+/// A data directory of its own for every storage, deleted with `temp_dir`.
+fn file_data_dir(temp_dir: &TempDir) -> PathBuf {
+    temp_dir
+        .path()
+        .join(format!("scheduler-test-{}", uuid::Uuid::new_v4()))
+}
+
+async fn setup_file_storage(data_dir: &Path, clock: Arc<dyn Clock>) -> Arc<FileStorage> {
+    Arc::new(
+        FileStorage::new(data_dir)
+            .await
+            .expect("Failed to create FileStorage")
+            .with_clock(clock),
     )
 }
 
+/// Waits until `condition` holds, so that tests driven by a [`ManualClock`] don't have to
+/// guess how long the scheduler needs to react.
+async fn wait_until<F, Fut>(mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Condition was not met in time");
+}
+
+/// Advances `clock` by `step` until `condition` holds, for deadlines that the scheduler
+/// takes from the clock at a moment the test can't observe.
+async fn advance_until<F, Fut>(clock: &ManualClock, step: Duration, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    wait_until(|| {
+        clock.advance(step);
+        condition()
+    })
+    .await;
+}
+
+/// A clock that only moves when the test advances it, and an in-memory storage reading it.
+fn manual_clock_storage() -> (Arc<ManualClock>, Arc<InMemoryStorage>) {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let storage = Arc::new(InMemoryStorage::new().with_clock(clock.clone()));
+    (clock, storage)
+}
+
 async fn get_run_tasks<S: Storage + ?Sized>(storage: &Arc<S>) -> usize {
     storage
         .get_all_tasks()
@@ -137,12 +262,10 @@ async fn get_run_tasks<S: Storage + ?Sized>(storage: &Arc<S>) -> usize {
 
 #[tokio::test]
 async fn test_add_and_execute_task() {
-    let storage = Arc::new(InMemoryStorage::new());
+    let (clock, storage) = manual_clock_storage();
     let registry = create_test_registry();
-    let scheduler = TaskScheduler::new(storage.clone(), registry)
-        .with_check_interval(time::Duration::from_millis(50));
-    let now = chrono::Utc::now();
-    let next_run = now + chrono::Duration::milliseconds(10);
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+    let next_run = clock.now() + chrono::Duration::milliseconds(10);
 
     let action = TaskAction::Log {
         message: "Test task".to_string(),
@@ -159,63 +282,63 @@ async fn test_add_and_execute_task() {
     assert_eq!(run_tasks, 0);
 
     scheduler.start().await.unwrap();
+    clock.advance(Duration::from_millis(10));
 
-    tokio::time::sleep(Duration::from_millis(100)).await;
-
-    let run_tasks = get_run_tasks(&storage).await;
-    assert_eq!(run_tasks, 1);
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
 }
 
 #[tokio::test]
 async fn test_retry_task_on_failure() {
-    let storage = Arc::new(InMemoryStorage::new());
+    let (clock, storage) = manual_clock_storage();
     let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
 
     let mut registry = ActionRegistry::new();
     registry.register(FailCountingExecutor::new(attempt_counter.clone(), 3));
 
-    let scheduler = TaskScheduler::new(storage.clone(), registry)
-        .with_check_interval(time::Duration::from_millis(50));
-    let now = chrono::Utc::now();
-    let next_run = now + chrono::Duration::milliseconds(10);
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
 
     let action = TaskAction::Log {
         message: "Test retry task".to_string(),
         level: "info".to_string(),
     };
 
-    scheduler
+    let task_id = scheduler
         .add_task(
-            Task::new_with_datetime(next_run, action)
+            Task::new_with_datetime(clock.now(), action)
                 .with_max_retries(3)
-                .with_retry_delay(Duration::from_millis(10)),
+                .with_retry_delay(Duration::from_secs(60))
+                .with_retry_policy(RetryPolicy::fixed()),
         )
         .await
         .unwrap();
 
-    let run_tasks = get_run_tasks(&storage).await;
-    assert_eq!(run_tasks, 0);
-
     scheduler.start().await.unwrap();
 
-    tokio::time::sleep(Duration::from_millis(300)).await;
+    // Every failed attempt waits for the retry delay to pass on the clock.
+    for attempts in 1..3u32 {
+        wait_until(|| async {
+            storage.get_task_runs(task_id).await.unwrap().len() == attempts as usize
+        })
+        .await;
+        assert_eq!(*attempt_counter.lock().await, attempts);
+        assert_eq!(get_run_tasks(&storage).await, 0);
 
-    let run_tasks = get_run_tasks(&storage).await;
-    assert_eq!(run_tasks, 1);
+        clock.advance(Duration::from_secs(60));
+    }
+
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
 
     let final_attempts = *attempt_counter.lock().await;
     assert_eq!(final_attempts, 3);
 }
 
-#[tokio::test]
-async fn test_execute_unfinished_tasks_on_startup() {
-    let (_pool, container) = setup_database().await;
-    let storage = setup_db_storage(&container).await;
+async fn assert_unfinished_tasks_are_executed_on_startup<S: Storage + 'static>(
+    storage: Arc<S>,
+    clock: Arc<ManualClock>,
+) {
     let registry = create_test_registry();
-    let scheduler = TaskScheduler::new(storage.clone(), registry)
-        .with_check_interval(time::Duration::from_millis(50));
-    let now = chrono::Utc::now();
-    let next_run = now - chrono::Duration::days(1);
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+    let next_run = clock.now() - chrono::Duration::days(1);
 
     let action = TaskAction::Log {
         message: "Test startup task".to_string(),
@@ -231,22 +354,34 @@ async fn test_execute_unfinished_tasks_on_startup() {
 
     scheduler.start().await.unwrap();
 
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
-    let run_tasks = get_run_tasks(&storage).await;
-    assert_eq!(run_tasks, 1);
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
 }
 
 #[tokio::test]
-async fn test_do_not_execute_disabled_tasks() {
+async fn test_database_unfinished_tasks_are_executed_on_startup() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
     let (_pool, container) = setup_database().await;
-    let storage = setup_db_storage(&container).await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    assert_unfinished_tasks_are_executed_on_startup(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_unfinished_tasks_are_executed_on_startup() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_sqlite_storage(&sqlite_database_url(&temp_dir), clock.clone()).await;
+    assert_unfinished_tasks_are_executed_on_startup(storage, clock).await;
+}
+
+async fn assert_disabled_tasks_are_not_executed<S: Storage + 'static>(
+    storage: Arc<S>,
+    clock: Arc<ManualClock>,
+) {
     let registry = create_test_registry();
-    let scheduler = TaskScheduler::new(storage.clone(), registry)
-        .with_check_interval(time::Duration::from_millis(50));
-    let now = chrono::Utc::now();
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+    let now = clock.now();
 
-    storage
+    let disabled_task_id = storage
         .save_task(Task {
             id: uuid::Uuid::new_v4(),
             schedule: TaskType::Once,
@@ -268,12 +403,54 @@ async fn test_do_not_execute_disabled_tasks() {
     let run_tasks = get_run_tasks(&storage).await;
     assert_eq!(run_tasks, 0);
 
+    // An enabled task that was due just as long, once it ran the disabled one would have too.
+    let action = TaskAction::Log {
+        message: "Enabled task".to_string(),
+        level: "info".to_string(),
+    };
+    let enabled_task_id = storage
+        .save_task(Task::new_with_datetime(
+            now - chrono::Duration::days(1),
+            action,
+        ))
+        .await
+        .unwrap();
+
     scheduler.start().await.unwrap();
 
-    tokio::time::sleep(Duration::from_millis(200)).await;
+    wait_until(|| async {
+        storage
+            .get_task(enabled_task_id)
+            .await
+            .unwrap()
+            .is_some_and(|task| task.last_run.is_some())
+    })
+    .await;
 
-    let run_tasks = get_run_tasks(&storage).await;
-    assert_eq!(run_tasks, 0);
+    assert_eq!(get_run_tasks(&storage).await, 1);
+    assert!(
+        storage
+            .get_task_runs(disabled_task_id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_database_disabled_tasks_are_not_executed() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let (_pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    assert_disabled_tasks_are_not_executed(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_disabled_tasks_are_not_executed() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_sqlite_storage(&sqlite_database_url(&temp_dir), clock.clone()).await;
+    assert_disabled_tasks_are_not_executed(storage, clock).await;
 }
 
 #[derive(Clone)]
@@ -304,13 +481,12 @@ impl ActionExecutor for CountingExecutor {
 
 #[tokio::test]
 async fn test_execute_range_based_task() {
-    let storage = Arc::new(InMemoryStorage::new());
+    let (clock, storage) = manual_clock_storage();
     let counting_executor = CountingExecutor::new();
     let mut registry = ActionRegistry::new();
     registry.register(counting_executor.clone());
-    let scheduler = TaskScheduler::new(storage.clone(), registry)
-        .with_check_interval(time::Duration::from_millis(50));
-    let now = chrono::Utc::now();
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+    let now = clock.now();
     let start_date = now + chrono::Duration::milliseconds(50);
     let end_date = now + chrono::Duration::milliseconds(100);
 
@@ -319,7 +495,7 @@ async fn test_execute_range_based_task() {
         level: "info".to_string(),
     };
 
-    scheduler
+    let task_id = scheduler
         .add_task(
             Task::new_with_datetime_range(start_date, end_date, action)
                 .with_delay_between_runs(chrono::Duration::milliseconds(40)),
@@ -332,7 +508,11 @@ async fn test_execute_range_based_task() {
 
     scheduler.start().await.unwrap();
 
-    tokio::time::sleep(Duration::from_millis(150)).await;
+    // Runs at 50 and 90ms, the next occurrence at 130ms is past the end date.
+    clock.advance(Duration::from_millis(50));
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+    clock.advance(Duration::from_millis(40));
+    wait_until(|| async { !storage.get_task(task_id).await.unwrap().unwrap().enabled }).await;
 
     let run_tasks = get_run_tasks(&storage).await;
     let count = *counting_executor.counter.lock().await;
@@ -340,3 +520,2582 @@ async fn test_execute_range_based_task() {
     assert_eq!(run_tasks, 1);
     assert_eq!(count, 2);
 }
+
+#[tokio::test]
+async fn test_execute_cron_based_task() {
+    let (clock, storage) = manual_clock_storage();
+    let counting_executor = CountingExecutor::new();
+    let mut registry = ActionRegistry::new();
+    registry.register(counting_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Cron-based task".to_string(),
+        level: "info".to_string(),
+    };
+
+    let task_id = scheduler
+        .add_task(
+            Task::new_with_cron_at("* * * * * * *", chrono_tz::UTC, action, clock.now()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    scheduler.start().await.unwrap();
+    clock.advance(Duration::from_secs(1));
+
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+    assert_eq!(*counting_executor.counter.lock().await, 1);
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert!(task.enabled);
+    assert!(task.next_run > clock.now());
+}
+
+#[test]
+fn test_cron_task_round_trips_through_db_task() {
+    use chrono::{Datelike, Timelike};
+
+    let action = TaskAction::Log {
+        message: "Weekday reminder".to_string(),
+        level: "info".to_string(),
+    };
+    let task =
+        Task::new_with_cron("0 30 8 * * Mon-Fri *", chrono_tz::Europe::Sarajevo, action).unwrap();
+
+    let local_next_run = task.next_run.with_timezone(&chrono_tz::Europe::Sarajevo);
+    assert_eq!((local_next_run.hour(), local_next_run.minute()), (8, 30));
+    assert!(local_next_run.weekday().number_from_monday() <= 5);
+
+    let restored = Task::from_db_task(task.to_db_task().unwrap()).unwrap();
+
+    match restored.schedule {
+        TaskType::Cron { expression } => assert_eq!(expression, "0 30 8 * * Mon-Fri *"),
+        other => panic!("Expected a cron schedule, got {:?}", other),
+    }
+    assert_eq!(restored.timezone, chrono_tz::Europe::Sarajevo);
+    assert_eq!(restored.next_run, task.next_run);
+}
+
+#[test]
+fn test_invalid_cron_expression_is_rejected() {
+    let action = TaskAction::Log {
+        message: "Invalid".to_string(),
+        level: "info".to_string(),
+    };
+
+    let result = Task::new_with_cron("not a cron expression", chrono_tz::UTC, action);
+    assert!(matches!(result, Err(SchedulerError::CronError(_))));
+}
+
+fn sarajevo_datetime(
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+) -> chrono::DateTime<chrono::Utc> {
+    use chrono::TimeZone;
+
+    chrono_tz::Europe::Sarajevo
+        .with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .earliest()
+        .unwrap()
+        .with_timezone(&chrono::Utc)
+}
+
+fn utc_datetime(
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+) -> chrono::DateTime<chrono::Utc> {
+    use chrono::TimeZone;
+
+    chrono::Utc
+        .with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .unwrap()
+}
+
+fn create_daily_sarajevo_task(start_date: chrono::DateTime<chrono::Utc>) -> Task {
+    let action = TaskAction::Log {
+        message: "Daily reminder".to_string(),
+        level: "info".to_string(),
+    };
+
+    Task::new_with_datetime_range(start_date, start_date + chrono::Duration::days(30), action)
+        .with_timezone(chrono_tz::Europe::Sarajevo)
+}
+
+#[test]
+fn test_range_task_keeps_local_time_across_dst_switch() {
+    // Clocks go forward on 31.03.2030, so 09:00 moves from 08:00 UTC to 07:00 UTC.
+    let mut task = create_daily_sarajevo_task(sarajevo_datetime(2030, 3, 30, 9, 0));
+    assert_eq!(task.next_run, utc_datetime(2030, 3, 30, 8, 0));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 3, 31, 7, 0));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 4, 1, 7, 0));
+}
+
+#[test]
+fn test_range_task_shifts_skipped_local_time_forward() {
+    // 02:30 does not exist on 31.03.2030, it runs at 03:30 instead and goes back to 02:30 after.
+    let mut task = create_daily_sarajevo_task(sarajevo_datetime(2030, 3, 30, 2, 30));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 3, 31, 1, 30));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 4, 1, 0, 30));
+}
+
+#[test]
+fn test_range_task_runs_ambiguous_local_time_once() {
+    // 02:30 happens twice on 27.10.2030, the task only runs at the first one.
+    let mut task = create_daily_sarajevo_task(sarajevo_datetime(2030, 10, 26, 2, 30));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 10, 27, 0, 30));
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 10, 28, 1, 30));
+}
+
+#[test]
+fn test_cron_task_keeps_local_time_across_dst_switch() {
+    let action = TaskAction::Log {
+        message: "Cron reminder".to_string(),
+        level: "info".to_string(),
+    };
+    let mut task = Task {
+        next_run: utc_datetime(2030, 10, 26, 7, 0),
+        ..Task::new_with_cron("0 0 9 * * * *", chrono_tz::Europe::Sarajevo, action).unwrap()
+    };
+
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 10, 27, 8, 0));
+}
+
+#[test]
+fn test_skip_to_latest_occurrence_of_range_task() {
+    // Runs daily at 09:00 from 20.03.2030 until 19.04.2030, across the switch to summer time.
+    let mut task = create_daily_sarajevo_task(sarajevo_datetime(2030, 3, 20, 9, 0));
+
+    task.skip_to_latest_occurrence(utc_datetime(2030, 4, 10, 12, 0))
+        .unwrap();
+    assert_eq!(task.next_run, sarajevo_datetime(2030, 4, 10, 9, 0));
+
+    task.skip_to_latest_occurrence(utc_datetime(2030, 6, 1, 0, 0))
+        .unwrap();
+    assert_eq!(task.next_run, sarajevo_datetime(2030, 4, 19, 9, 0));
+}
+
+#[test]
+fn test_skip_to_latest_occurrence_of_range_task_with_sub_day_delay() {
+    let start_date = utc_datetime(2030, 1, 1, 0, 0);
+    let action = TaskAction::Log {
+        message: "Frequent reminder".to_string(),
+        level: "info".to_string(),
+    };
+    let mut task =
+        Task::new_with_datetime_range(start_date, start_date + chrono::Duration::days(30), action)
+            .with_delay_between_runs(chrono::Duration::minutes(7));
+
+    task.skip_to_latest_occurrence(start_date + chrono::Duration::minutes(1000))
+        .unwrap();
+    assert_eq!(task.next_run, start_date + chrono::Duration::minutes(994));
+
+    // A next run moved past the latest occurrence is kept.
+    task.next_run = start_date + chrono::Duration::minutes(995);
+    task.skip_to_latest_occurrence(start_date + chrono::Duration::minutes(1000))
+        .unwrap();
+    assert_eq!(task.next_run, start_date + chrono::Duration::minutes(995));
+}
+
+#[test]
+fn test_skip_to_latest_occurrence_of_cron_task() {
+    let action = TaskAction::Log {
+        message: "Cron reminder".to_string(),
+        level: "info".to_string(),
+    };
+    let mut task = Task {
+        next_run: sarajevo_datetime(2030, 3, 1, 9, 0),
+        ..Task::new_with_cron("0 0 9 * * * *", chrono_tz::Europe::Sarajevo, action).unwrap()
+    };
+
+    task.skip_to_latest_occurrence(sarajevo_datetime(2030, 4, 10, 8, 30))
+        .unwrap();
+    assert_eq!(task.next_run, sarajevo_datetime(2030, 4, 9, 9, 0));
+
+    task.skip_to_latest_occurrence(sarajevo_datetime(2030, 4, 10, 9, 0))
+        .unwrap();
+    assert_eq!(task.next_run, sarajevo_datetime(2030, 4, 10, 9, 0));
+}
+
+#[tokio::test]
+async fn test_task_is_executed_once_by_multiple_schedulers() {
+    let (clock, storage) = manual_clock_storage();
+    let counting_executor = CountingExecutor::new();
+
+    let mut schedulers = Vec::new();
+    for _ in 0..3 {
+        let mut registry = ActionRegistry::new();
+        registry.register(counting_executor.clone());
+        schedulers.push(TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone()));
+    }
+
+    let action = TaskAction::Log {
+        message: "Shared task".to_string(),
+        level: "info".to_string(),
+    };
+
+    schedulers[0]
+        .add_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    let mut handles = Vec::new();
+    for scheduler in &schedulers {
+        handles.push(scheduler.start().await.unwrap());
+    }
+
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+
+    // Stopping waits for the executions, so a second one would have been counted.
+    for (scheduler, handle) in schedulers.iter().zip(handles) {
+        scheduler.stop().await.unwrap();
+        handle.await.unwrap();
+    }
+
+    let count = *counting_executor.counter.lock().await;
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn test_claim_skips_leased_tasks_until_lease_expires() {
+    let (clock, storage) = manual_clock_storage();
+    let action = TaskAction::Log {
+        message: "Leased task".to_string(),
+        level: "info".to_string(),
+    };
+
+    storage
+        .save_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    let claimed = storage
+        .claim_ready_tasks("crashed-worker", Duration::from_millis(50), 100)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+
+    let claimed = storage
+        .claim_ready_tasks("other-worker", Duration::from_millis(50), 100)
+        .await
+        .unwrap();
+    assert!(claimed.is_empty());
+
+    clock.advance(Duration::from_millis(50));
+
+    let claimed = storage
+        .claim_ready_tasks("other-worker", Duration::from_millis(50), 100)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+}
+
+async fn assert_claim_leases_tasks_to_a_single_worker<S: Storage>(storage: &S, other_storage: &S) {
+    let now = chrono::Utc::now();
+
+    for i in 0..10 {
+        let action = TaskAction::Log {
+            message: format!("Task {}", i),
+            level: "info".to_string(),
+        };
+        storage
+            .save_task(Task::new_with_datetime(now, action))
+            .await
+            .unwrap();
+    }
+
+    let (claimed, other_claimed) = tokio::join!(
+        storage.claim_ready_tasks("worker-1", Duration::from_secs(1), 100),
+        other_storage.claim_ready_tasks("worker-2", Duration::from_secs(1), 100),
+    );
+    let (claimed, other_claimed) = (claimed.unwrap(), other_claimed.unwrap());
+
+    assert_eq!(claimed.len() + other_claimed.len(), 10);
+    assert!(
+        claimed
+            .iter()
+            .all(|task| other_claimed.iter().all(|other| other.id != task.id))
+    );
+
+    let released_task = claimed.first().or(other_claimed.first()).unwrap().id;
+    let owner = if claimed.is_empty() {
+        "worker-2"
+    } else {
+        "worker-1"
+    };
+    storage.release_task(released_task, owner).await.unwrap();
+
+    let reclaimed = storage
+        .claim_ready_tasks("worker-3", Duration::from_secs(1), 100)
+        .await
+        .unwrap();
+    assert_eq!(reclaimed.len(), 1);
+    assert_eq!(reclaimed[0].id, released_task);
+}
+
+#[tokio::test]
+async fn test_database_claim_leases_tasks_to_a_single_worker() {
+    let (_pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, Arc::new(SystemClock)).await;
+    let other_storage = setup_db_storage(&container, Arc::new(SystemClock)).await;
+    assert_claim_leases_tasks_to_a_single_worker(storage.as_ref(), other_storage.as_ref()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_claim_leases_tasks_to_a_single_worker() {
+    let temp_dir = TempDir::new().unwrap();
+    let database_url = sqlite_database_url(&temp_dir);
+    let storage = setup_sqlite_storage(&database_url, Arc::new(SystemClock)).await;
+    let other_storage = setup_sqlite_storage(&database_url, Arc::new(SystemClock)).await;
+    assert_claim_leases_tasks_to_a_single_worker(storage.as_ref(), other_storage.as_ref()).await;
+}
+
+#[tokio::test]
+async fn test_redis_claim_leases_tasks_to_a_single_worker() {
+    let container = setup_redis().await;
+    let storage = setup_redis_storage(&container, Arc::new(SystemClock)).await;
+    let other_storage = setup_redis_storage(&container, Arc::new(SystemClock)).await;
+    assert_claim_leases_tasks_to_a_single_worker(storage.as_ref(), other_storage.as_ref()).await;
+}
+
+#[tokio::test]
+async fn test_added_task_wakes_up_idle_scheduler() {
+    let (clock, storage) = manual_clock_storage();
+    let counting_executor = CountingExecutor::new();
+    let mut registry = ActionRegistry::new();
+    registry.register(counting_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_check_interval(time::Duration::from_secs(60))
+        .with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "First task".to_string(),
+        level: "info".to_string(),
+    };
+    scheduler
+        .add_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    // Once the first task ran, the scheduler sleeps until the end of its check interval.
+    wait_until(|| async { *counting_executor.counter.lock().await == 1 }).await;
+
+    let action = TaskAction::Log {
+        message: "Wake up".to_string(),
+        level: "info".to_string(),
+    };
+    scheduler
+        .add_task(Task::new_with_datetime(
+            clock.now() + chrono::Duration::milliseconds(50),
+            action,
+        ))
+        .await
+        .unwrap();
+    clock.advance(Duration::from_millis(50));
+
+    wait_until(|| async { *counting_executor.counter.lock().await == 2 }).await;
+}
+
+#[tokio::test]
+async fn test_task_saved_by_another_process_wakes_up_scheduler() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let (pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    let other_process_storage = setup_db_storage(&container, clock.clone()).await;
+    let counting_executor = CountingExecutor::new();
+    let mut registry = ActionRegistry::new();
+    registry.register(counting_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_check_interval(time::Duration::from_secs(60))
+        .with_clock(clock.clone());
+
+    // The scheduler listens for changes by the time it was started.
+    let handle = scheduler.start().await.unwrap();
+
+    let action = TaskAction::Log {
+        message: "Added by another process".to_string(),
+        level: "info".to_string(),
+    };
+    other_process_storage
+        .save_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    wait_until(|| async { *counting_executor.counter.lock().await == 1 }).await;
+
+    // Stopping closes the connection the scheduler listened on.
+    scheduler.stop().await.unwrap();
+    handle.await.unwrap();
+    wait_until(|| async {
+        let listeners: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM pg_stat_activity WHERE query LIKE 'LISTEN%'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        listeners == 0
+    })
+    .await;
+}
+
+/// Test executor that takes `duration` on `clock` to execute a task
+#[derive(Clone)]
+struct SlowExecutor {
+    clock: Arc<ManualClock>,
+    duration: Duration,
+    started: Arc<AtomicUsize>,
+    counter: Arc<tokio::sync::Mutex<u32>>,
+}
+
+impl SlowExecutor {
+    fn new(clock: Arc<ManualClock>, duration: Duration) -> Self {
+        Self {
+            clock,
+            duration,
+            started: Arc::new(AtomicUsize::new(0)),
+            counter: Arc::new(tokio::sync::Mutex::new(0)),
+        }
+    }
+
+    async fn wait_until_started(&self, executions: usize) {
+        wait_until(|| async { self.started.load(Ordering::SeqCst) == executions }).await;
+    }
+}
+
+#[async_trait]
+impl ActionExecutor for SlowExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::Log]
+    }
+
+    async fn execute(&self, _task: &Task, _action: &TaskAction) -> Result<(), SchedulerError> {
+        // Counted as started only once the deadline is set, so advancing the clock after
+        // that always finishes it.
+        let deadline = after(self.clock.now(), self.duration);
+        self.started.fetch_add(1, Ordering::SeqCst);
+        self.clock.sleep_until(deadline).await;
+        let mut count = self.counter.lock().await;
+        *count += 1;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_stop_waits_for_running_tasks() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_millis(200));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Slow task".to_string(),
+        level: "info".to_string(),
+    };
+    let task_id = scheduler
+        .add_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    let handle = scheduler.start().await.unwrap();
+    slow_executor.wait_until_started(1).await;
+    scheduler.stop().await.unwrap();
+    clock.advance(Duration::from_millis(200));
+    handle.await.unwrap();
+
+    assert_eq!(*slow_executor.counter.lock().await, 1);
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert!(task.last_run.is_some());
+    assert!(!task.enabled);
+}
+
+#[tokio::test]
+async fn test_stop_persists_retry_state_of_waiting_task() {
+    let (clock, storage) = manual_clock_storage();
+    let counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(counter.clone(), 10));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Failing task".to_string(),
+        level: "info".to_string(),
+    };
+    let task_id = scheduler
+        .add_task(
+            Task::new_with_datetime(clock.now(), action).with_retry_delay(Duration::from_secs(60)),
+        )
+        .await
+        .unwrap();
+
+    let handle = scheduler.start().await.unwrap();
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+    scheduler.stop().await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(1), handle)
+        .await
+        .expect("Stopping should not wait for the retry delay")
+        .unwrap();
+
+    assert_eq!(*counter.lock().await, 1);
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert_eq!(task.retry_count, 1);
+    assert!(task.enabled);
+    assert!(task.last_run.is_none());
+
+    let claimed = storage
+        .claim_ready_tasks("next-worker", Duration::from_secs(1), 100)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].retry_count, 1);
+}
+
+#[tokio::test]
+async fn test_stop_releases_tasks_unfinished_after_shutdown_timeout() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_secs(24 * 60 * 60));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_shutdown_timeout(Duration::from_millis(100))
+        .with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Hanging task".to_string(),
+        level: "info".to_string(),
+    };
+    scheduler
+        .add_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    let handle = scheduler.start().await.unwrap();
+    slow_executor.wait_until_started(1).await;
+    scheduler.stop().await.unwrap();
+
+    // The shutdown timeout starts once the scheduler noticed the stop.
+    let handle = tokio::spawn(handle);
+    advance_until(&clock, Duration::from_millis(100), || async {
+        handle.is_finished()
+    })
+    .await;
+    handle.await.unwrap().unwrap();
+
+    assert_eq!(*slow_executor.counter.lock().await, 0);
+
+    let claimed = storage
+        .claim_ready_tasks("next-worker", Duration::from_secs(1), 100)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert!(claimed[0].last_run.is_none());
+}
+
+/// Test executor that takes 30ms on `clock` per task and records the most tasks it was
+/// executing at the same time
+#[derive(Clone)]
+struct ConcurrencyTrackingExecutor {
+    clock: Arc<ManualClock>,
+    running: Arc<AtomicUsize>,
+    max_running: Arc<AtomicUsize>,
+    executed: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyTrackingExecutor {
+    fn new(clock: Arc<ManualClock>) -> Self {
+        Self {
+            clock,
+            running: Arc::new(AtomicUsize::new(0)),
+            max_running: Arc::new(AtomicUsize::new(0)),
+            executed: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionExecutor for ConcurrencyTrackingExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::Log]
+    }
+
+    async fn execute(&self, _task: &Task, _action: &TaskAction) -> Result<(), SchedulerError> {
+        let deadline = after(self.clock.now(), Duration::from_millis(30));
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        self.clock.sleep_until(deadline).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        self.executed.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+async fn add_overdue_log_tasks(scheduler: &TaskScheduler, count: usize) {
+    for i in 0..count {
+        let action = TaskAction::Log {
+            message: format!("Overdue task {}", i),
+            level: "info".to_string(),
+        };
+        scheduler
+            .add_task(Task::new_with_datetime(
+                scheduler.clock().now() - chrono::Duration::hours(1),
+                action,
+            ))
+            .await
+            .unwrap();
+    }
+}
+
+/// Lets `executor` finish its tasks once `limit` of them run at the same time.
+async fn execute_tasks_up_to_limit(
+    clock: &ManualClock,
+    executor: &ConcurrencyTrackingExecutor,
+    limit: usize,
+    count: usize,
+) {
+    wait_until(|| async { executor.running.load(Ordering::SeqCst) == limit }).await;
+    advance_until(clock, Duration::from_millis(30), || async {
+        executor.executed.load(Ordering::SeqCst) == count
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_max_concurrency_limits_running_tasks() {
+    let (clock, storage) = manual_clock_storage();
+    let executor = ConcurrencyTrackingExecutor::new(clock.clone());
+    let mut registry = ActionRegistry::new();
+    registry.register(executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_check_interval(time::Duration::from_secs(60))
+        .with_max_concurrency(3)
+        .with_batch_size(2)
+        .with_clock(clock.clone());
+
+    add_overdue_log_tasks(&scheduler, 10).await;
+
+    scheduler.start().await.unwrap();
+
+    execute_tasks_up_to_limit(&clock, &executor, 3, 10).await;
+
+    assert_eq!(executor.max_running.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_action_concurrency_limits_running_tasks_of_action_type() {
+    let (clock, storage) = manual_clock_storage();
+    let executor = ConcurrencyTrackingExecutor::new(clock.clone());
+    let mut registry = ActionRegistry::new();
+    registry.register(executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_check_interval(time::Duration::from_secs(60))
+        .with_action_concurrency(ActionType::Log, 1)
+        .with_clock(clock.clone());
+
+    add_overdue_log_tasks(&scheduler, 5).await;
+
+    scheduler.start().await.unwrap();
+
+    execute_tasks_up_to_limit(&clock, &executor, 1, 5).await;
+
+    assert_eq!(executor.max_running.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_task_waiting_for_action_concurrency_keeps_its_lease() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_millis(80));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_action_concurrency(ActionType::Log, 1)
+        .with_lease_duration(Duration::from_millis(100))
+        .with_clock(clock.clone());
+
+    add_overdue_log_tasks(&scheduler, 2).await;
+
+    let handle = scheduler.start().await.unwrap();
+    slow_executor.wait_until_started(1).await;
+    clock.advance(Duration::from_millis(80));
+    slow_executor.wait_until_started(2).await;
+
+    // The lease the second task was claimed with has expired by now.
+    clock.advance(Duration::from_millis(40));
+    let claimed = storage
+        .claim_ready_tasks("other-worker", Duration::from_secs(60), 100)
+        .await
+        .unwrap();
+    assert!(claimed.is_empty());
+
+    scheduler.stop().await.unwrap();
+    clock.advance(Duration::from_millis(40));
+    handle.await.unwrap();
+
+    assert_eq!(*slow_executor.counter.lock().await, 2);
+}
+
+async fn assert_every_attempt_is_recorded<S: Storage + 'static>(
+    storage: Arc<S>,
+    clock: Arc<ManualClock>,
+) {
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), 2));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Recorded task".to_string(),
+        level: "info".to_string(),
+    };
+    let task_id = scheduler
+        .add_task(
+            Task::new_with_datetime(clock.now(), action)
+                .with_retry_delay(Duration::from_millis(10))
+                .with_retry_policy(RetryPolicy::fixed()),
+        )
+        .await
+        .unwrap();
+
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+    clock.advance(Duration::from_millis(10));
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+
+    let runs = storage.get_task_runs(task_id).await.unwrap();
+    assert_eq!(runs.len(), 2);
+
+    assert_eq!(runs[0].attempt, 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Failed);
+    assert_eq!(
+        runs[0].error.as_deref(),
+        Some("Task execution error: Simulated failure")
+    );
+    assert!(runs[0].started_at <= runs[0].finished_at);
+
+    assert_eq!(runs[1].attempt, 2);
+    assert_eq!(runs[1].outcome, TaskRunOutcome::Succeeded);
+    assert_eq!(runs[1].error, None);
+}
+
+#[tokio::test]
+async fn test_executing_task_keeps_its_lease() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_secs(1));
+    let mut schedulers = Vec::new();
+    for _ in 0..2 {
+        let mut registry = ActionRegistry::new();
+        registry.register(slow_executor.clone());
+        schedulers.push(
+            TaskScheduler::new(storage.clone(), registry)
+                .with_lease_duration(Duration::from_millis(100))
+                .with_clock(clock.clone()),
+        );
+    }
+
+    let action = TaskAction::Log {
+        message: "Long task".to_string(),
+        level: "info".to_string(),
+    };
+    schedulers[0]
+        .add_task(Task::new_with_datetime(clock.now(), action))
+        .await
+        .unwrap();
+
+    let mut handles = vec![schedulers[0].start().await.unwrap()];
+    slow_executor.wait_until_started(1).await;
+    handles.push(schedulers[1].start().await.unwrap());
+
+    // Only moves on once the lease was renewed past the next step.
+    for _ in 0..19 {
+        clock.advance(Duration::from_millis(50));
+        wait_until(|| async {
+            let next_due_time = storage.next_due_time().await.unwrap().unwrap();
+            next_due_time > after(clock.now(), Duration::from_millis(50))
+        })
+        .await;
+    }
+    clock.advance(Duration::from_millis(50));
+    wait_until(|| async { *slow_executor.counter.lock().await == 1 }).await;
+
+    for (scheduler, handle) in schedulers.iter().zip(handles) {
+        scheduler.stop().await.unwrap();
+        handle.await.unwrap();
+    }
+    assert_eq!(slow_executor.started.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_task_waiting_for_retry_keeps_its_lease() {
+    let (clock, storage) = manual_clock_storage();
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), 2));
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_lease_duration(Duration::from_millis(100))
+        .with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Retried task".to_string(),
+        level: "info".to_string(),
+    };
+    scheduler
+        .add_task(
+            Task::new_with_datetime(clock.now(), action)
+                .with_retry_delay(Duration::from_secs(1))
+                .with_retry_policy(RetryPolicy::fixed()),
+        )
+        .await
+        .unwrap();
+
+    scheduler.start().await.unwrap();
+
+    // The lease covers the retry delay once it's renewed.
+    wait_until(|| async {
+        let next_due_time = storage.next_due_time().await.unwrap().unwrap();
+        next_due_time > after(clock.now(), Duration::from_millis(100))
+    })
+    .await;
+    clock.advance(Duration::from_millis(500));
+    let claimed = storage
+        .claim_ready_tasks("other-worker", Duration::from_secs(60), 100)
+        .await
+        .unwrap();
+    assert!(claimed.is_empty());
+
+    clock.advance(Duration::from_millis(500));
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+    assert_eq!(*attempt_counter.lock().await, 2);
+}
+
+#[tokio::test]
+async fn test_every_attempt_is_recorded() {
+    let (clock, storage) = manual_clock_storage();
+    assert_every_attempt_is_recorded(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_database_every_attempt_is_recorded() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let (_pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    assert_every_attempt_is_recorded(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_every_attempt_is_recorded() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_sqlite_storage(&sqlite_database_url(&temp_dir), clock.clone()).await;
+    assert_every_attempt_is_recorded(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_redis_every_attempt_is_recorded() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let container = setup_redis().await;
+    let storage = setup_redis_storage(&container, clock.clone()).await;
+    assert_every_attempt_is_recorded(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_file_every_attempt_is_recorded() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_file_storage(&file_data_dir(&temp_dir), clock.clone()).await;
+    assert_every_attempt_is_recorded(storage, clock).await;
+}
+
+/// A task due at `now` that is retried once, right away.
+fn create_failing_task(now: chrono::DateTime<chrono::Utc>) -> Task {
+    let action = TaskAction::Log {
+        message: "Failing task".to_string(),
+        level: "info".to_string(),
+    };
+    Task::new_with_datetime(now, action)
+        .with_max_retries(2)
+        .with_retry_delay(Duration::ZERO)
+}
+
+async fn assert_exhausted_task_is_dead_lettered<S: Storage + 'static>(
+    storage: Arc<S>,
+    clock: Arc<ManualClock>,
+) {
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), u32::MAX));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !scheduler.list_dead_letters().await.unwrap().is_empty() }).await;
+
+    let dead_letters = scheduler.list_dead_letters().await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].task.id, task_id);
+    assert_eq!(dead_letters[0].attempts, 2);
+    assert_eq!(
+        dead_letters[0].error,
+        "Task execution error: Simulated failure"
+    );
+    assert!(matches!(
+        dead_letters[0].task.action,
+        Some(TaskAction::Log { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_exhausted_task_is_dead_lettered() {
+    let (clock, storage) = manual_clock_storage();
+    assert_exhausted_task_is_dead_lettered(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_database_exhausted_task_is_dead_lettered() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let (_pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    assert_exhausted_task_is_dead_lettered(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_exhausted_task_is_dead_lettered() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_sqlite_storage(&sqlite_database_url(&temp_dir), clock.clone()).await;
+    assert_exhausted_task_is_dead_lettered(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_redis_exhausted_task_is_dead_lettered() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let container = setup_redis().await;
+    let storage = setup_redis_storage(&container, clock.clone()).await;
+    assert_exhausted_task_is_dead_lettered(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_file_exhausted_task_is_dead_lettered() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_file_storage(&file_data_dir(&temp_dir), clock.clone()).await;
+    assert_exhausted_task_is_dead_lettered(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_retry_dead_letter_runs_task_again() {
+    let (clock, storage) = manual_clock_storage();
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), 3));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !scheduler.list_dead_letters().await.unwrap().is_empty() }).await;
+
+    let dead_letters = scheduler.list_dead_letters().await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+
+    let retried_task_id = scheduler
+        .retry_dead_letter(dead_letters[0].id)
+        .await
+        .unwrap();
+
+    wait_until(|| async {
+        storage
+            .get_task(retried_task_id)
+            .await
+            .unwrap()
+            .is_some_and(|task| task.last_run.is_some())
+    })
+    .await;
+
+    assert_eq!(*attempt_counter.lock().await, 3);
+    assert!(scheduler.list_dead_letters().await.unwrap().is_empty());
+}
+
+/// Delegates to an in-memory storage, failing the operations it's told to.
+#[derive(Default)]
+struct FailingStorage {
+    storage: InMemoryStorage,
+    fail_task_saves: AtomicBool,
+    fail_dead_letter_deletes: AtomicBool,
+}
+
+fn simulated_failure() -> SchedulerError {
+    SchedulerError::DatabaseError("Simulated failure".to_string())
+}
+
+#[async_trait]
+impl Storage for FailingStorage {
+    async fn save_task(&self, task: Task) -> Result<uuid::Uuid, SchedulerError> {
+        if self.fail_task_saves.load(Ordering::SeqCst) {
+            return Err(simulated_failure());
+        }
+        self.storage.save_task(task).await
+    }
+
+    async fn get_task(&self, id: uuid::Uuid) -> Result<Option<Task>, SchedulerError> {
+        self.storage.get_task(id).await
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<Task>, SchedulerError> {
+        self.storage.get_all_tasks().await
+    }
+
+    async fn delete_task(&self, id: uuid::Uuid) -> Result<(), SchedulerError> {
+        self.storage.delete_task(id).await
+    }
+
+    async fn set_task_enabled(&self, id: uuid::Uuid, enabled: bool) -> Result<(), SchedulerError> {
+        self.storage.set_task_enabled(id, enabled).await
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: uuid::Uuid,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), SchedulerError> {
+        self.storage.set_task_next_run(id, next_run).await
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: uuid::Uuid,
+        schedule: TaskType,
+        next_run: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), SchedulerError> {
+        self.storage.set_task_schedule(id, schedule, next_run).await
+    }
+
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage, SchedulerError> {
+        self.storage.query_tasks(query).await
+    }
+
+    async fn get_ready_tasks(&self) -> Result<Vec<Task>, SchedulerError> {
+        self.storage.get_ready_tasks().await
+    }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, SchedulerError> {
+        self.storage
+            .claim_ready_tasks(worker_id, lease, limit)
+            .await
+    }
+
+    async fn release_task(&self, id: uuid::Uuid, worker_id: &str) -> Result<(), SchedulerError> {
+        self.storage.release_task(id, worker_id).await
+    }
+
+    async fn renew_lease(
+        &self,
+        id: uuid::Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), SchedulerError> {
+        self.storage.renew_lease(id, worker_id, lease).await
+    }
+
+    async fn next_due_time(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>, SchedulerError> {
+        self.storage.next_due_time().await
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), SchedulerError> {
+        self.storage.record_task_run(run).await
+    }
+
+    async fn get_task_runs(&self, task_id: uuid::Uuid) -> Result<Vec<TaskRun>, SchedulerError> {
+        self.storage.get_task_runs(task_id).await
+    }
+
+    async fn save_dead_letter(&self, dead_letter: DeadLetter) -> Result<(), SchedulerError> {
+        self.storage.save_dead_letter(dead_letter).await
+    }
+
+    async fn get_dead_letter(&self, id: uuid::Uuid) -> Result<Option<DeadLetter>, SchedulerError> {
+        self.storage.get_dead_letter(id).await
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, SchedulerError> {
+        self.storage.get_dead_letters().await
+    }
+
+    async fn delete_dead_letter(&self, id: uuid::Uuid) -> Result<(), SchedulerError> {
+        if self.fail_dead_letter_deletes.load(Ordering::SeqCst) {
+            return Err(simulated_failure());
+        }
+        self.storage.delete_dead_letter(id).await
+    }
+}
+
+#[tokio::test]
+async fn test_retrying_dead_letter_fails_without_side_effects() {
+    let storage = Arc::new(FailingStorage::default());
+    let scheduler = TaskScheduler::new(storage.clone(), create_test_registry());
+    let dead_letter = DeadLetter::new(
+        create_failing_task(chrono::Utc::now()),
+        &simulated_failure(),
+        chrono::Utc::now(),
+    );
+    storage.save_dead_letter(dead_letter.clone()).await.unwrap();
+
+    storage
+        .fail_dead_letter_deletes
+        .store(true, Ordering::SeqCst);
+    assert!(scheduler.retry_dead_letter(dead_letter.id).await.is_err());
+    assert!(storage.get_all_tasks().await.unwrap().is_empty());
+    storage
+        .fail_dead_letter_deletes
+        .store(false, Ordering::SeqCst);
+
+    storage.fail_task_saves.store(true, Ordering::SeqCst);
+    assert!(scheduler.retry_dead_letter(dead_letter.id).await.is_err());
+    assert!(storage.get_all_tasks().await.unwrap().is_empty());
+    storage.fail_task_saves.store(false, Ordering::SeqCst);
+
+    // The dead letter is still there after both failures, and is retried only once.
+    scheduler.retry_dead_letter(dead_letter.id).await.unwrap();
+    assert!(scheduler.retry_dead_letter(dead_letter.id).await.is_err());
+    assert_eq!(storage.get_all_tasks().await.unwrap().len(), 1);
+    assert!(scheduler.list_dead_letters().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_discard_dead_letter() {
+    let (clock, storage) = manual_clock_storage();
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), u32::MAX));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !scheduler.list_dead_letters().await.unwrap().is_empty() }).await;
+
+    let dead_letter_id = scheduler.list_dead_letters().await.unwrap()[0].id;
+    scheduler.discard_dead_letter(dead_letter_id).await.unwrap();
+
+    assert!(scheduler.list_dead_letters().await.unwrap().is_empty());
+    assert!(matches!(
+        scheduler.discard_dead_letter(dead_letter_id).await,
+        Err(SchedulerError::DeadLetterNotFound(_))
+    ));
+    assert!(matches!(
+        scheduler.retry_dead_letter(dead_letter_id).await,
+        Err(SchedulerError::DeadLetterNotFound(_))
+    ));
+}
+
+/// Test executor that records the bot messages it was asked to send
+#[derive(Clone, Default)]
+struct RecordingBotExecutor {
+    messages: Arc<tokio::sync::Mutex<Vec<(i64, String)>>>,
+}
+
+#[async_trait]
+impl ActionExecutor for RecordingBotExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::SendBotMessage]
+    }
+
+    async fn execute(&self, _task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
+        if let TaskAction::SendBotMessage { chat_id, message } = action {
+            self.messages.lock().await.push((*chat_id, message.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_dead_letter_action_is_executed() {
+    let (clock, storage) = manual_clock_storage();
+    let bot_executor = RecordingBotExecutor::default();
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(
+        Arc::new(tokio::sync::Mutex::new(0)),
+        u32::MAX,
+    ));
+    registry.register(bot_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_clock(clock.clone())
+        .with_dead_letter_action(|dead_letter| TaskAction::SendBotMessage {
+            chat_id: 42,
+            message: format!("Task {} failed", dead_letter.task.id),
+        });
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !bot_executor.messages.lock().await.is_empty() }).await;
+
+    let messages = bot_executor.messages.lock().await;
+    assert_eq!(*messages, vec![(42, format!("Task {} failed", task_id))]);
+}
+
+/// Test executor for bot messages that panics, or hangs until its timeout if `hang` is set
+#[derive(Clone, Default)]
+struct BrokenBotExecutor {
+    hang: bool,
+    started: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl ActionExecutor for BrokenBotExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::SendBotMessage]
+    }
+
+    async fn execute(&self, _task: &Task, _action: &TaskAction) -> Result<(), SchedulerError> {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        if self.hang {
+            std::future::pending::<()>().await;
+        }
+        panic!("notification exploded");
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+}
+
+/// Dead-letters a task that fails on its only attempt, notifying through `bot_executor`.
+async fn dead_letter_with_notification(
+    bot_executor: BrokenBotExecutor,
+) -> (
+    Arc<ManualClock>,
+    Arc<InMemoryStorage>,
+    TaskScheduler,
+    uuid::Uuid,
+) {
+    let (clock, storage) = manual_clock_storage();
+    let task = create_failing_task(clock.now()).with_max_retries(0);
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(
+        Arc::new(tokio::sync::Mutex::new(0)),
+        u32::MAX,
+    ));
+    registry.register(bot_executor);
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_clock(clock.clone())
+        .with_dead_letter_action(|_| TaskAction::SendBotMessage {
+            chat_id: 42,
+            message: "Task failed".to_string(),
+        });
+
+    let task_id = scheduler.add_task(task).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    (clock, storage, scheduler, task_id)
+}
+
+#[tokio::test]
+async fn test_panicking_dead_letter_action_does_not_keep_task_from_being_saved() {
+    let bot_executor = BrokenBotExecutor::default();
+    let (_clock, storage, scheduler, task_id) =
+        dead_letter_with_notification(bot_executor.clone()).await;
+
+    wait_until(|| async {
+        storage
+            .get_task(task_id)
+            .await
+            .unwrap()
+            .is_some_and(|task| !task.enabled)
+    })
+    .await;
+
+    assert_eq!(bot_executor.started.load(Ordering::SeqCst), 1);
+    assert_eq!(scheduler.list_dead_letters().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_hanging_dead_letter_action_times_out() {
+    let bot_executor = BrokenBotExecutor {
+        hang: true,
+        ..Default::default()
+    };
+    let (clock, storage, scheduler, task_id) =
+        dead_letter_with_notification(bot_executor.clone()).await;
+
+    wait_until(|| async { bot_executor.started.load(Ordering::SeqCst) == 1 }).await;
+    assert!(storage.get_task(task_id).await.unwrap().unwrap().enabled);
+
+    clock.advance(Duration::from_secs(1));
+    wait_until(|| async { !storage.get_task(task_id).await.unwrap().unwrap().enabled }).await;
+
+    assert_eq!(scheduler.list_dead_letters().await.unwrap().len(), 1);
+    assert!(
+        storage
+            .claim_ready_tasks("other-worker", Duration::from_secs(1), 100)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_retry_policy_delays() {
+    let base = Duration::from_secs(1);
+
+    assert_eq!(RetryPolicy::fixed().delay(base, 3), Duration::from_secs(1));
+    assert_eq!(RetryPolicy::linear().delay(base, 3), Duration::from_secs(3));
+    assert_eq!(
+        RetryPolicy::exponential().delay(base, 3),
+        Duration::from_secs(8)
+    );
+    assert_eq!(
+        RetryPolicy::exponential()
+            .with_max_delay(Duration::from_secs(5))
+            .delay(base, 3),
+        Duration::from_secs(5)
+    );
+    assert_eq!(
+        RetryPolicy::exponential().delay(base, u32::MAX),
+        Duration::from_secs(60 * 60)
+    );
+
+    for retry_count in 1..10 {
+        let delay = RetryPolicy::exponential_with_jitter()
+            .with_max_delay(Duration::from_secs(30))
+            .delay(base, retry_count);
+        assert!(delay <= Duration::from_secs(2_u64.pow(retry_count).min(30)));
+    }
+}
+
+#[test]
+fn test_retry_policy_round_trips_through_db_task() {
+    let action = TaskAction::Log {
+        message: "Retried task".to_string(),
+        level: "info".to_string(),
+    };
+    let policy = RetryPolicy::linear().with_max_delay(Duration::from_secs(90));
+    let task = Task::new_with_datetime(chrono::Utc::now(), action.clone())
+        .with_retry_policy(policy.clone());
+
+    let restored = Task::from_db_task(task.to_db_task().unwrap()).unwrap();
+    assert_eq!(restored.retry_policy, Some(policy));
+
+    let task = Task::new_with_datetime(chrono::Utc::now(), action);
+    let restored = Task::from_db_task(task.to_db_task().unwrap()).unwrap();
+    assert_eq!(restored.retry_policy, None);
+}
+
+/// Test executor that fails like [`FailCountingExecutor`] and retries with a fixed delay
+struct FixedRetryExecutor(FailCountingExecutor);
+
+#[async_trait]
+impl ActionExecutor for FixedRetryExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        self.0.supported_actions()
+    }
+
+    async fn execute(&self, task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
+        self.0.execute(task, action).await
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        Some(RetryPolicy::fixed())
+    }
+}
+
+#[tokio::test]
+async fn test_executor_retry_policy_is_used_when_task_has_none() {
+    let (clock, storage) = manual_clock_storage();
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FixedRetryExecutor(FailCountingExecutor::new(
+        attempt_counter.clone(),
+        3,
+    )));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Retried task".to_string(),
+        level: "info".to_string(),
+    };
+    // Exponential backoff would wait 200 and 400ms instead of 100ms before each retry.
+    let task_id = scheduler
+        .add_task(
+            Task::new_with_datetime(clock.now(), action)
+                .with_retry_delay(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+
+    scheduler.start().await.unwrap();
+
+    for attempts in 1..3 {
+        wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == attempts })
+            .await;
+        clock.advance(Duration::from_millis(100));
+    }
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+
+    assert_eq!(*attempt_counter.lock().await, 3);
+}
+
+/// Test executor that returns the given results in order, and succeeds afterwards
+#[derive(Clone)]
+struct ScriptedExecutor {
+    results: Arc<tokio::sync::Mutex<VecDeque<Result<(), SchedulerError>>>>,
+    counter: Arc<tokio::sync::Mutex<u32>>,
+}
+
+impl ScriptedExecutor {
+    fn new(results: Vec<Result<(), SchedulerError>>) -> Self {
+        Self {
+            results: Arc::new(tokio::sync::Mutex::new(results.into())),
+            counter: Arc::new(tokio::sync::Mutex::new(0)),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionExecutor for ScriptedExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::Log]
+    }
+
+    async fn execute(&self, _task: &Task, _action: &TaskAction) -> Result<(), SchedulerError> {
+        *self.counter.lock().await += 1;
+        self.results.lock().await.pop_front().unwrap_or(Ok(()))
+    }
+}
+
+#[test]
+fn test_error_disposition() {
+    assert_eq!(
+        SchedulerError::TaskExecutionError("timeout".into()).disposition(),
+        ErrorDisposition::Retryable
+    );
+    assert_eq!(
+        SchedulerError::PermanentExecutionError("bot was blocked".into()).disposition(),
+        ErrorDisposition::Permanent
+    );
+    assert_eq!(
+        SchedulerError::ActionMissing("task".into()).disposition(),
+        ErrorDisposition::Permanent
+    );
+    assert_eq!(
+        SchedulerError::RetryAfter(Duration::from_secs(5)).disposition(),
+        ErrorDisposition::RetryAfter(Duration::from_secs(5))
+    );
+}
+
+#[tokio::test]
+async fn test_permanent_error_is_not_retried() {
+    let (clock, storage) = manual_clock_storage();
+    let executor = ScriptedExecutor::new(vec![Err(SchedulerError::PermanentExecutionError(
+        "Forbidden: bot was blocked by the user".into(),
+    ))]);
+    let mut registry = ActionRegistry::new();
+    registry.register(executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !scheduler.list_dead_letters().await.unwrap().is_empty() }).await;
+
+    assert_eq!(*executor.counter.lock().await, 1);
+
+    let dead_letters = scheduler.list_dead_letters().await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].task.id, task_id);
+    assert_eq!(dead_letters[0].attempts, 1);
+}
+
+#[tokio::test]
+async fn test_retry_after_error_overrides_retry_delay() {
+    let (clock, storage) = manual_clock_storage();
+    let executor = ScriptedExecutor::new(vec![Err(SchedulerError::RetryAfter(
+        Duration::from_millis(50),
+    ))]);
+    let mut registry = ActionRegistry::new();
+    registry.register(executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Rate limited task".to_string(),
+        level: "info".to_string(),
+    };
+    let task_id = scheduler
+        .add_task(
+            Task::new_with_datetime(clock.now(), action).with_retry_delay(Duration::from_secs(60)),
+        )
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+    clock.advance(Duration::from_millis(50));
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+
+    assert_eq!(*executor.counter.lock().await, 2);
+}
+
+/// Test executor that returns the same outcome for every task
+struct OutcomeExecutor {
+    outcome: ExecutionOutcome,
+}
+
+#[async_trait]
+impl ActionExecutor for OutcomeExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::Log]
+    }
+
+    async fn execute(&self, task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
+        self.execute_with_outcome(task, action).await.map(|_| ())
+    }
+
+    async fn execute_with_outcome(
+        &self,
+        _task: &Task,
+        _action: &TaskAction,
+    ) -> Result<ExecutionOutcome, SchedulerError> {
+        Ok(self.outcome.clone())
+    }
+}
+
+/// Runs a daily task that is due at `now` once, with an executor returning `outcome`.
+async fn execute_daily_task_with_outcome(
+    now: chrono::DateTime<chrono::Utc>,
+    outcome: ExecutionOutcome,
+) -> (Task, Task, Vec<TaskRun>) {
+    let clock = Arc::new(ManualClock::new(now));
+    let storage = Arc::new(InMemoryStorage::new().with_clock(clock.clone()));
+    let mut registry = ActionRegistry::new();
+    registry.register(OutcomeExecutor { outcome });
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let action = TaskAction::Log {
+        message: "Daily task".to_string(),
+        level: "info".to_string(),
+    };
+    let task = Task::new_with_datetime_range(now, now + chrono::Duration::days(7), action);
+    scheduler.add_task(task.clone()).await.unwrap();
+    let handle = scheduler.start().await.unwrap();
+
+    // Stopping waits for the execution, including saving the task.
+    wait_until(|| async { storage.get_task_runs(task.id).await.unwrap().len() == 1 }).await;
+    scheduler.stop().await.unwrap();
+    handle.await.unwrap();
+
+    let executed_task = storage.get_task(task.id).await.unwrap().unwrap();
+    let runs = storage.get_task_runs(task.id).await.unwrap();
+    (task, executed_task, runs)
+}
+
+#[tokio::test]
+async fn test_completed_outcome_records_output() {
+    let (task, executed_task, runs) = execute_daily_task_with_outcome(
+        chrono::Utc::now(),
+        ExecutionOutcome::Completed {
+            output: Some("Message 42 sent".to_string()),
+        },
+    )
+    .await;
+
+    assert!(executed_task.last_run.is_some());
+    assert_eq!(
+        executed_task.next_run,
+        task.next_run + chrono::Duration::days(1)
+    );
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Succeeded);
+    assert_eq!(runs[0].output.as_deref(), Some("Message 42 sent"));
+}
+
+#[tokio::test]
+async fn test_skipped_outcome_moves_to_next_occurrence() {
+    let (task, executed_task, runs) =
+        execute_daily_task_with_outcome(chrono::Utc::now(), ExecutionOutcome::Skipped).await;
+
+    assert!(executed_task.last_run.is_none());
+    assert!(executed_task.enabled);
+    assert_eq!(
+        executed_task.next_run,
+        task.next_run + chrono::Duration::days(1)
+    );
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Skipped);
+}
+
+#[tokio::test]
+async fn test_disable_outcome_disables_task() {
+    let (task, executed_task, _) =
+        execute_daily_task_with_outcome(chrono::Utc::now(), ExecutionOutcome::Disable).await;
+
+    assert!(!executed_task.enabled);
+    assert!(executed_task.last_run.is_none());
+    assert_eq!(executed_task.next_run, task.next_run);
+}
+
+#[tokio::test]
+async fn test_reschedule_outcome_sets_next_run() {
+    let now = chrono::Utc::now();
+    let at = now + chrono::Duration::hours(3);
+    let (_, executed_task, _) =
+        execute_daily_task_with_outcome(now, ExecutionOutcome::Reschedule { at }).await;
+
+    assert!(executed_task.enabled);
+    assert_eq!(executed_task.next_run, at);
+}
+
+#[tokio::test]
+async fn test_hung_task_times_out_and_is_retried() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_secs(60));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()).with_timeout(Duration::from_millis(50)))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    for attempt in 1..=2 {
+        slow_executor.wait_until_started(attempt).await;
+        clock.advance(Duration::from_millis(50));
+    }
+    wait_until(|| async { !storage.get_task(task_id).await.unwrap().unwrap().enabled }).await;
+
+    let runs = storage.get_task_runs(task_id).await.unwrap();
+    assert_eq!(runs.len(), 2);
+    assert!(
+        runs.iter()
+            .all(|run| run.outcome == TaskRunOutcome::TimedOut)
+    );
+
+    let dead_letters = scheduler.list_dead_letters().await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(
+        dead_letters[0].error,
+        SchedulerError::Timeout(Duration::from_millis(50)).to_string()
+    );
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert!(!task.enabled);
+    assert!(
+        storage
+            .claim_ready_tasks("other-worker", Duration::from_secs(1), 100)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+/// Test executor that hangs like [`SlowExecutor`] and times out after 50ms by default
+struct TimeoutExecutor(SlowExecutor);
+
+#[async_trait]
+impl ActionExecutor for TimeoutExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        self.0.supported_actions()
+    }
+
+    async fn execute(&self, task: &Task, action: &TaskAction) -> Result<(), SchedulerError> {
+        self.0.execute(task, action).await
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+}
+
+#[tokio::test]
+async fn test_executor_timeout_is_used_when_task_has_none() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_secs(60));
+    let mut registry = ActionRegistry::new();
+    registry.register(TimeoutExecutor(slow_executor.clone()));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    slow_executor.wait_until_started(1).await;
+    clock.advance(Duration::from_millis(50));
+    wait_until(|| async { !storage.get_task_runs(task_id).await.unwrap().is_empty() }).await;
+
+    let runs = storage.get_task_runs(task_id).await.unwrap();
+    assert!(!runs.is_empty());
+    assert_eq!(runs[0].outcome, TaskRunOutcome::TimedOut);
+}
+
+#[test]
+fn test_timeout_round_trips_through_db_task() {
+    let action = TaskAction::Log {
+        message: "Timed task".to_string(),
+        level: "info".to_string(),
+    };
+    let task = Task::new_with_datetime(chrono::Utc::now(), action)
+        .with_timeout(Duration::from_millis(1500));
+
+    let restored = Task::from_db_task(task.to_db_task().unwrap()).unwrap();
+    assert_eq!(restored.timeout, Some(Duration::from_millis(1500)));
+}
+
+/// Test executor that panics on the first `panics` attempts
+struct PanickingExecutor {
+    attempts: Arc<AtomicUsize>,
+    panics: usize,
+}
+
+#[async_trait]
+impl ActionExecutor for PanickingExecutor {
+    fn supported_actions(&self) -> Vec<ActionType> {
+        vec![ActionType::Log]
+    }
+
+    async fn execute(&self, _task: &Task, _action: &TaskAction) -> Result<(), SchedulerError> {
+        if self.attempts.fetch_add(1, Ordering::SeqCst) < self.panics {
+            panic!("executor exploded");
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_executor_panic_is_a_failed_attempt() {
+    let (clock, storage) = manual_clock_storage();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(PanickingExecutor {
+        attempts: attempts.clone(),
+        panics: 1,
+    });
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { get_run_tasks(&storage).await == 1 }).await;
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    let runs = storage.get_task_runs(task_id).await.unwrap();
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Failed);
+    assert_eq!(
+        runs[0].error.as_deref(),
+        Some("Task executor panicked: executor exploded")
+    );
+    assert_eq!(runs[1].outcome, TaskRunOutcome::Succeeded);
+}
+
+#[tokio::test]
+async fn test_scheduler_keeps_running_after_executor_panics() {
+    let (clock, storage) = manual_clock_storage();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(PanickingExecutor {
+        attempts: attempts.clone(),
+        panics: usize::MAX,
+    });
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !scheduler.list_dead_letters().await.unwrap().is_empty() }).await;
+
+    let dead_letters = scheduler.list_dead_letters().await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].task.id, task_id);
+    assert_eq!(
+        dead_letters[0].error,
+        "Task executor panicked: executor exploded"
+    );
+
+    let next_task_id = scheduler
+        .add_task(create_failing_task(clock.now()))
+        .await
+        .unwrap();
+
+    wait_until(|| async { scheduler.list_dead_letters().await.unwrap().len() == 2 }).await;
+    assert_eq!(storage.get_task_runs(next_task_id).await.unwrap().len(), 2);
+}
+
+#[test]
+fn test_executor_panic_is_retryable() {
+    assert_eq!(
+        SchedulerError::ExecutorPanicked("boom".into()).disposition(),
+        ErrorDisposition::Retryable
+    );
+}
+
+/// Runs a daily task whose last four occurrences were missed, the latest one an hour ago.
+async fn execute_missed_daily_task(misfire_policy: MisfirePolicy) -> (Task, Task, Vec<TaskRun>) {
+    let (clock, storage) = manual_clock_storage();
+    let scheduler =
+        TaskScheduler::new(storage.clone(), create_test_registry()).with_clock(clock.clone());
+
+    let start_date = clock.now() - chrono::Duration::days(3) - chrono::Duration::hours(1);
+    let action = TaskAction::Log {
+        message: "Missed daily task".to_string(),
+        level: "info".to_string(),
+    };
+    let task =
+        Task::new_with_datetime_range(start_date, start_date + chrono::Duration::days(30), action)
+            .with_misfire_policy(misfire_policy);
+    scheduler.add_task(task.clone()).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    // Every missed occurrence was handled once the task is due in the future.
+    wait_until(|| async {
+        storage.get_task(task.id).await.unwrap().unwrap().next_run > clock.now()
+    })
+    .await;
+
+    let executed_task = storage.get_task(task.id).await.unwrap().unwrap();
+    let runs = storage.get_task_runs(task.id).await.unwrap();
+    (task, executed_task, runs)
+}
+
+#[tokio::test]
+async fn test_fire_once_misfire_policy_runs_latest_missed_occurrence() {
+    let (task, executed_task, runs) = execute_missed_daily_task(MisfirePolicy::FireOnce).await;
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Succeeded);
+    assert!(executed_task.last_run.is_some());
+    assert_eq!(
+        executed_task.next_run,
+        task.next_run + chrono::Duration::days(4)
+    );
+}
+
+#[tokio::test]
+async fn test_skip_misfire_policy_moves_to_next_future_occurrence() {
+    let (task, executed_task, runs) = execute_missed_daily_task(MisfirePolicy::Skip).await;
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Skipped);
+    assert!(executed_task.last_run.is_none());
+    assert!(executed_task.enabled);
+    assert_eq!(
+        executed_task.next_run,
+        task.next_run + chrono::Duration::days(4)
+    );
+}
+
+#[tokio::test]
+async fn test_fire_all_misfire_policy_runs_every_missed_occurrence() {
+    let (task, executed_task, runs) = execute_missed_daily_task(MisfirePolicy::FireAll).await;
+
+    assert_eq!(runs.len(), 4);
+    assert!(
+        runs.iter()
+            .all(|run| run.outcome == TaskRunOutcome::Succeeded)
+    );
+    assert_eq!(
+        executed_task.next_run,
+        task.next_run + chrono::Duration::days(4)
+    );
+}
+
+#[tokio::test]
+async fn test_drop_older_than_misfire_policy() {
+    let (_, _, runs) =
+        execute_missed_daily_task(MisfirePolicy::DropOlderThan(Duration::from_secs(30 * 60))).await;
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Skipped);
+
+    let (_, _, runs) = execute_missed_daily_task(MisfirePolicy::DropOlderThan(
+        Duration::from_secs(2 * 60 * 60),
+    ))
+    .await;
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Succeeded);
+}
+
+#[tokio::test]
+async fn test_waiting_for_action_concurrency_does_not_misfire_task() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_secs(2 * 60));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry)
+        .with_action_concurrency(ActionType::Log, 1)
+        .with_misfire_threshold(Duration::from_secs(60))
+        .with_clock(clock.clone());
+
+    for i in 0..2 {
+        let action = TaskAction::Log {
+            message: format!("Queued task {}", i),
+            level: "info".to_string(),
+        };
+        scheduler
+            .add_task(
+                Task::new_with_datetime(clock.now(), action)
+                    .with_misfire_policy(MisfirePolicy::Skip),
+            )
+            .await
+            .unwrap();
+    }
+
+    let handle = scheduler.start().await.unwrap();
+    slow_executor.wait_until_started(1).await;
+
+    // The second task waits for the permit longer than the misfire threshold.
+    clock.advance(Duration::from_secs(2 * 60));
+    slow_executor.wait_until_started(2).await;
+
+    scheduler.stop().await.unwrap();
+    clock.advance(Duration::from_secs(2 * 60));
+    handle.await.unwrap();
+
+    assert_eq!(*slow_executor.counter.lock().await, 2);
+    assert_eq!(get_run_tasks(&storage).await, 2);
+}
+
+#[tokio::test]
+async fn test_tasks_within_misfire_threshold_are_not_misfired() {
+    let (clock, storage) = manual_clock_storage();
+    let scheduler = TaskScheduler::new(storage.clone(), create_test_registry())
+        .with_misfire_threshold(Duration::from_secs(2 * 60 * 60))
+        .with_clock(clock.clone());
+
+    let now = clock.now();
+    let action = TaskAction::Log {
+        message: "Late task".to_string(),
+        level: "info".to_string(),
+    };
+    let task = Task::new_with_datetime_range(
+        now - chrono::Duration::hours(1),
+        now + chrono::Duration::days(7),
+        action,
+    )
+    .with_misfire_policy(MisfirePolicy::Skip);
+    scheduler.add_task(task.clone()).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    wait_until(|| async { !storage.get_task_runs(task.id).await.unwrap().is_empty() }).await;
+
+    let runs = storage.get_task_runs(task.id).await.unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].outcome, TaskRunOutcome::Succeeded);
+}
+
+#[test]
+fn test_misfire_policy_round_trips_through_db_task() {
+    let action = TaskAction::Log {
+        message: "Missed task".to_string(),
+        level: "info".to_string(),
+    };
+    let task = Task::new_with_datetime(chrono::Utc::now(), action)
+        .with_misfire_policy(MisfirePolicy::DropOlderThan(Duration::from_secs(600)));
+
+    let restored = Task::from_db_task(task.to_db_task().unwrap()).unwrap();
+    assert_eq!(
+        restored.misfire_policy,
+        MisfirePolicy::DropOlderThan(Duration::from_secs(600))
+    );
+}
+
+fn create_daily_task(start_date: chrono::DateTime<chrono::Utc>) -> Task {
+    let action = TaskAction::Log {
+        message: "Managed task".to_string(),
+        level: "info".to_string(),
+    };
+    Task::new_with_datetime_range(start_date, start_date + chrono::Duration::days(7), action)
+}
+
+#[tokio::test]
+async fn test_paused_task_runs_after_resume() {
+    let (clock, storage) = manual_clock_storage();
+    let scheduler =
+        TaskScheduler::new(storage.clone(), create_test_registry()).with_clock(clock.clone());
+
+    let next_run = clock.now() + chrono::Duration::milliseconds(100);
+    let task_id = scheduler
+        .add_task(create_daily_task(next_run))
+        .await
+        .unwrap();
+    // Runs at the same time as the paused task would, once it ran the paused one would have too.
+    let other_task_id = scheduler
+        .add_task(create_daily_task(next_run))
+        .await
+        .unwrap();
+    scheduler.pause_task(task_id).await.unwrap();
+    assert!(matches!(
+        scheduler.pause_task(task_id).await,
+        Err(SchedulerError::InvalidTaskState(_))
+    ));
+    assert!(matches!(
+        scheduler.run_now(task_id).await,
+        Err(SchedulerError::InvalidTaskState(_))
+    ));
+
+    scheduler.start().await.unwrap();
+    clock.advance(Duration::from_millis(100));
+    wait_until(|| async {
+        storage
+            .get_task(other_task_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_run
+            .is_some()
+    })
+    .await;
+    assert!(storage.get_task_runs(task_id).await.unwrap().is_empty());
+
+    scheduler.resume_task(task_id).await.unwrap();
+    assert!(matches!(
+        scheduler.resume_task(task_id).await,
+        Err(SchedulerError::InvalidTaskState(_))
+    ));
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+}
+
+#[tokio::test]
+async fn test_task_management_requires_existing_task() {
+    let scheduler = TaskScheduler::new(Arc::new(InMemoryStorage::new()), create_test_registry());
+    let id = uuid::Uuid::new_v4();
+
+    assert!(matches!(
+        scheduler.pause_task(id).await,
+        Err(SchedulerError::TaskNotFound(_))
+    ));
+    assert!(matches!(
+        scheduler.cancel_task(id).await,
+        Err(SchedulerError::TaskNotFound(_))
+    ));
+    assert!(matches!(
+        scheduler.run_now(id).await,
+        Err(SchedulerError::TaskNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_run_now_keeps_schedule() {
+    let (clock, storage) = manual_clock_storage();
+    let scheduler =
+        TaskScheduler::new(storage.clone(), create_test_registry()).with_clock(clock.clone());
+
+    let task = create_daily_task(clock.now() + chrono::Duration::hours(1));
+    scheduler.add_task(task.clone()).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    scheduler.run_now(task.id).await.unwrap();
+    wait_until(|| async {
+        storage
+            .get_task(task.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_run
+            .is_some()
+    })
+    .await;
+
+    let executed_task = storage.get_task(task.id).await.unwrap().unwrap();
+    assert_eq!(storage.get_task_runs(task.id).await.unwrap().len(), 1);
+    assert_eq!(executed_task.next_run, task.next_run);
+}
+
+#[tokio::test]
+async fn test_reschedule_task() {
+    let storage = Arc::new(InMemoryStorage::new());
+    let scheduler = TaskScheduler::new(storage.clone(), create_test_registry());
+
+    let now = chrono::Utc::now();
+    let task_id = scheduler.add_task(create_daily_task(now)).await.unwrap();
+
+    let cron = TaskType::Cron {
+        expression: "not a cron".to_string(),
+    };
+    assert!(scheduler.reschedule_task(task_id, cron, now).await.is_err());
+
+    let range = TaskType::Range {
+        start_date: now + chrono::Duration::days(1),
+        end_date: now + chrono::Duration::days(2),
+    };
+    assert!(matches!(
+        scheduler.reschedule_task(task_id, range.clone(), now).await,
+        Err(SchedulerError::InvalidSchedule(_))
+    ));
+
+    let next_run = now + chrono::Duration::days(1);
+    scheduler
+        .reschedule_task(task_id, range, next_run)
+        .await
+        .unwrap();
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert_eq!(task.next_run, next_run);
+    assert!(matches!(task.schedule, TaskType::Range { start_date, .. } if start_date == next_run));
+}
+
+#[tokio::test]
+async fn test_pausing_executing_task_is_kept_after_execution() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_millis(150));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task_id = scheduler
+        .add_task(create_daily_task(clock.now()))
+        .await
+        .unwrap();
+    scheduler.start().await.unwrap();
+
+    slow_executor.wait_until_started(1).await;
+    scheduler.pause_task(task_id).await.unwrap();
+    clock.advance(Duration::from_millis(150));
+    wait_until(|| async {
+        storage
+            .get_task(task_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_run
+            .is_some()
+    })
+    .await;
+
+    let task = storage.get_task(task_id).await.unwrap().unwrap();
+    assert!(!task.enabled);
+}
+
+#[tokio::test]
+async fn test_cancelled_task_is_not_retried_or_saved() {
+    let (clock, storage) = manual_clock_storage();
+    let attempt_counter = Arc::new(tokio::sync::Mutex::new(0));
+    let mut registry = ActionRegistry::new();
+    registry.register(FailCountingExecutor::new(attempt_counter.clone(), u32::MAX));
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task = create_failing_task(clock.now())
+        .with_retry_policy(RetryPolicy::fixed())
+        .with_retry_delay(Duration::from_millis(100));
+    let task_id = scheduler.add_task(task).await.unwrap();
+    let handle = scheduler.start().await.unwrap();
+
+    wait_until(|| async { storage.get_task_runs(task_id).await.unwrap().len() == 1 }).await;
+    scheduler.cancel_task(task_id).await.unwrap();
+    clock.advance(Duration::from_millis(100));
+
+    // Stopping waits for the execution, so a retry would have been counted.
+    scheduler.stop().await.unwrap();
+    handle.await.unwrap();
+
+    assert_eq!(*attempt_counter.lock().await, 1);
+    assert!(storage.get_task(task_id).await.unwrap().is_none());
+    assert!(scheduler.list_dead_letters().await.unwrap().is_empty());
+}
+
+#[test]
+fn test_range_task_continues_from_start_date_after_moved_run() {
+    let start_date = utc_datetime(2030, 5, 1, 9, 0);
+    let mut task = Task {
+        next_run: utc_datetime(2030, 5, 3, 6, 0),
+        ..create_daily_task(start_date)
+    };
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, utc_datetime(2030, 5, 3, 9, 0));
+
+    let mut task = Task {
+        next_run: start_date + chrono::Duration::minutes(70),
+        ..create_daily_task(start_date).with_delay_between_runs(chrono::Duration::minutes(30))
+    };
+    task.calculate_next_run().unwrap();
+    assert_eq!(task.next_run, start_date + chrono::Duration::minutes(90));
+}
+
+fn create_task_with_metadata() -> Task {
+    let action = TaskAction::Log {
+        message: "Task with metadata".to_string(),
+        level: "info".to_string(),
+    };
+    Task::new_with_datetime(chrono::Utc::now() + chrono::Duration::hours(1), action).with_metadata(
+        TaskMetadata {
+            title: Some("Water the plants".to_string()),
+            description: Some("Both balconies".to_string()),
+            created_by: Some("42".to_string()),
+            assignee: Some("@user".to_string()),
+            chat_id: Some(-100),
+            tags: vec!["home".to_string(), "weekly".to_string()],
+        },
+    )
+}
+
+#[test]
+fn test_task_snapshot_without_metadata_deserializes() {
+    let mut snapshot = serde_json::to_value(create_task_with_metadata()).unwrap();
+    let fields = snapshot.as_object_mut().unwrap();
+    for field in ["metadata", "created_at", "updated_at", "misfire_policy"] {
+        fields.remove(field);
+    }
+
+    let task: Task = serde_json::from_value(snapshot).unwrap();
+    assert_eq!(task.metadata, TaskMetadata::default());
+    assert_eq!(task.misfire_policy, MisfirePolicy::FireOnce);
+}
+
+async fn assert_edits_during_execution_are_merged<S: Storage + 'static>(
+    storage: Arc<S>,
+    clock: Arc<ManualClock>,
+) {
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_millis(200));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let task = create_daily_task(clock.now());
+    scheduler.add_task(task.clone()).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    slow_executor.wait_until_started(1).await;
+    let mut edited = storage.get_task(task.id).await.unwrap().unwrap();
+    edited.metadata.title = Some("Edited while running".to_string());
+    storage.save_task(edited).await.unwrap();
+
+    clock.advance(Duration::from_millis(200));
+    wait_until(|| async {
+        storage
+            .get_task(task.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_run
+            .is_some()
+    })
+    .await;
+
+    let saved = storage.get_task(task.id).await.unwrap().unwrap();
+    assert_eq!(
+        saved.metadata.title.as_deref(),
+        Some("Edited while running")
+    );
+    assert!(saved.last_run.is_some());
+    assert_eq!(
+        saved.next_run.timestamp(),
+        (task.next_run + chrono::Duration::days(1)).timestamp()
+    );
+    assert_eq!(storage.get_task_runs(task.id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_edits_during_execution_are_merged() {
+    let (clock, storage) = manual_clock_storage();
+    assert_edits_during_execution_are_merged(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_database_edits_during_execution_are_merged() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let (_pool, container) = setup_database().await;
+    let storage = setup_db_storage(&container, clock.clone()).await;
+    assert_edits_during_execution_are_merged(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_edits_during_execution_are_merged() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_sqlite_storage(&sqlite_database_url(&temp_dir), clock.clone()).await;
+    assert_edits_during_execution_are_merged(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_redis_edits_during_execution_are_merged() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let container = setup_redis().await;
+    let storage = setup_redis_storage(&container, clock.clone()).await;
+    assert_edits_during_execution_are_merged(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_file_edits_during_execution_are_merged() {
+    let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+    let temp_dir = TempDir::new().unwrap();
+    let storage = setup_file_storage(&file_data_dir(&temp_dir), clock.clone()).await;
+    assert_edits_during_execution_are_merged(storage, clock).await;
+}
+
+#[tokio::test]
+async fn test_reschedule_during_execution_is_kept() {
+    let (clock, storage) = manual_clock_storage();
+    let slow_executor = SlowExecutor::new(clock.clone(), Duration::from_millis(150));
+    let mut registry = ActionRegistry::new();
+    registry.register(slow_executor.clone());
+    let scheduler = TaskScheduler::new(storage.clone(), registry).with_clock(clock.clone());
+
+    let now = clock.now();
+    let task_id = scheduler.add_task(create_daily_task(now)).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    slow_executor.wait_until_started(1).await;
+    let next_run = now + chrono::Duration::hours(5);
+    scheduler
+        .reschedule_task(
+            task_id,
+            TaskType::Range {
+                start_date: next_run,
+                end_date: next_run + chrono::Duration::days(3),
+            },
+            next_run,
+        )
+        .await
+        .unwrap();
+    clock.advance(Duration::from_millis(150));
+    wait_until(|| async {
+        storage
+            .get_task(task_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_run
+            .is_some()
+    })
+    .await;
+
+    let saved = storage.get_task(task_id).await.unwrap().unwrap();
+    assert_eq!(saved.next_run, next_run);
+}
+
+#[tokio::test]
+async fn test_file_storage_restores_state_after_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = file_data_dir(&temp_dir);
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    let action = TaskAction::Log {
+        message: "Stored task".to_string(),
+        level: "info".to_string(),
+    };
+    let task = create_task_with_metadata();
+    let deleted_task = Task::new_with_datetime(chrono::Utc::now(), action);
+
+    storage.save_task(task.clone()).await.unwrap();
+    storage.save_task(deleted_task.clone()).await.unwrap();
+    storage.set_task_enabled(task.id, false).await.unwrap();
+    storage.delete_task(deleted_task.id).await.unwrap();
+
+    let outcome = Ok(ExecutionOutcome::Completed { output: None });
+    storage
+        .record_task_run(TaskRun::new(
+            task.id,
+            1,
+            chrono::Utc::now(),
+            chrono::Utc::now(),
+            &outcome,
+        ))
+        .await
+        .unwrap();
+    let error = SchedulerError::TaskExecutionError("Failed".to_string());
+    let dead_letter = DeadLetter::new(task.clone(), &error, chrono::Utc::now());
+    storage.save_dead_letter(dead_letter.clone()).await.unwrap();
+    drop(storage);
+
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    let restored_task = storage.get_task(task.id).await.unwrap().unwrap();
+
+    assert!(!restored_task.enabled);
+    assert_eq!(restored_task.version, 2);
+    assert_eq!(restored_task.metadata, task.metadata);
+    assert!(storage.get_task(deleted_task.id).await.unwrap().is_none());
+    assert_eq!(storage.get_task_runs(task.id).await.unwrap().len(), 1);
+    assert!(
+        storage
+            .get_dead_letter(dead_letter.id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn test_file_storage_drops_truncated_tail() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = file_data_dir(&temp_dir);
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    let first_task = create_daily_task(chrono::Utc::now());
+    storage.save_task(first_task.clone()).await.unwrap();
+    drop(storage);
+
+    // A crash in the middle of a write leaves the last entry cut off.
+    let log_path = data_dir.join("tasks.log");
+    let mut log = std::fs::read(&log_path).unwrap();
+    let complete_len = log.len();
+    log.extend_from_slice(br#"{"TaskSaved":{"id":"#);
+    std::fs::write(&log_path, &log).unwrap();
+
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    assert_eq!(std::fs::read(&log_path).unwrap().len(), complete_len);
+    assert_eq!(storage.get_all_tasks().await.unwrap().len(), 1);
+
+    let second_task = create_daily_task(chrono::Utc::now());
+    storage.save_task(second_task.clone()).await.unwrap();
+    drop(storage);
+
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    assert!(storage.get_task(first_task.id).await.unwrap().is_some());
+    assert!(storage.get_task(second_task.id).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_file_storage_rejects_corrupt_entry() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = file_data_dir(&temp_dir);
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    storage
+        .save_task(create_daily_task(chrono::Utc::now()))
+        .await
+        .unwrap();
+    drop(storage);
+
+    let log_path = data_dir.join("tasks.log");
+    let log = std::fs::read(&log_path).unwrap();
+    let mut corrupt_log = b"not an entry\n".to_vec();
+    corrupt_log.extend_from_slice(&log);
+    std::fs::write(&log_path, &corrupt_log).unwrap();
+
+    assert!(FileStorage::new(&data_dir).await.is_err());
+}
+
+#[tokio::test]
+async fn test_file_storage_compacts_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = file_data_dir(&temp_dir);
+    let storage = Arc::new(
+        FileStorage::new(&data_dir)
+            .await
+            .unwrap()
+            .with_compaction_threshold(5),
+    );
+    let task = create_daily_task(chrono::Utc::now());
+    storage.save_task(task.clone()).await.unwrap();
+
+    for _ in 0..20 {
+        storage.set_task_enabled(task.id, true).await.unwrap();
+    }
+
+    let log = std::fs::read_to_string(data_dir.join("tasks.log")).unwrap();
+    assert!(log.lines().count() < 5);
+    drop(storage);
+
+    let storage = setup_file_storage(&data_dir, Arc::new(SystemClock)).await;
+    let restored_task = storage.get_task(task.id).await.unwrap().unwrap();
+    assert_eq!(restored_task.version, 21);
+}
+
+mod conformance {
+    use super::*;
+    use crate::storage::conformance::run_storage_conformance;
+
+    #[tokio::test]
+    async fn test_in_memory_storage_conformance() {
+        run_storage_conformance(|| async { Arc::new(InMemoryStorage::new()) }).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage_conformance() {
+        let temp_dir = TempDir::new().unwrap();
+        run_storage_conformance(|| async {
+            setup_sqlite_storage(&sqlite_database_url(&temp_dir), Arc::new(SystemClock)).await
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_conformance() {
+        let temp_dir = TempDir::new().unwrap();
+        run_storage_conformance(|| async {
+            setup_file_storage(&file_data_dir(&temp_dir), Arc::new(SystemClock)).await
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_storage_conformance() {
+        let (pool, container) = setup_database().await;
+        let (pool, container) = (&pool, &container);
+
+        run_storage_conformance(move || async move {
+            sqlx::query("TRUNCATE tasks, task_runs, dead_letters CASCADE")
+                .execute(pool)
+                .await
+                .expect("Failed to empty the database");
+            setup_db_storage(container, Arc::new(SystemClock)).await
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_redis_storage_conformance() {
+        let container = setup_redis().await;
+        let url = redis_url(&container).await;
+        let url = &url;
+
+        // Every storage gets its own key prefix, so it starts out empty.
+        run_storage_conformance(move || async move {
+            Arc::new(
+                RedisStorage::new(url)
+                    .await
+                    .unwrap()
+                    .with_key_prefix(uuid::Uuid::new_v4().to_string()),
+            )
+        })
+        .await;
+    }
+}
@@ -0,0 +1,166 @@
+//! Implements `Storage` from outside the crate, to make sure everything a third-party
+//! storage needs to pass the conformance suite is public.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use scheduler::{
+    error::SchedulerError,
+    storage::{
+        base_storage::Storage,
+        conformance::run_storage_conformance,
+        in_memory_storage::InMemoryStorage,
+        task_query::{TaskCursor, TaskPage, TaskQuery},
+    },
+    task::{
+        dead_letter::DeadLetter,
+        default::{Task, TaskType},
+        task_run::TaskRun,
+    },
+};
+use uuid::Uuid;
+
+/// Keeps the records in an [`InMemoryStorage`], but queries them the way a storage that
+/// translates the query into its own query language does.
+#[derive(Default)]
+struct ExternalStorage {
+    records: InMemoryStorage,
+}
+
+#[async_trait]
+impl Storage for ExternalStorage {
+    async fn save_task(&self, task: Task) -> Result<Uuid, SchedulerError> {
+        self.records.save_task(task).await
+    }
+
+    async fn get_task(&self, id: Uuid) -> Result<Option<Task>, SchedulerError> {
+        self.records.get_task(id).await
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<Task>, SchedulerError> {
+        self.records.get_all_tasks().await
+    }
+
+    async fn delete_task(&self, id: Uuid) -> Result<(), SchedulerError> {
+        self.records.delete_task(id).await
+    }
+
+    async fn set_task_enabled(&self, id: Uuid, enabled: bool) -> Result<(), SchedulerError> {
+        self.records.set_task_enabled(id, enabled).await
+    }
+
+    async fn set_task_next_run(
+        &self,
+        id: Uuid,
+        next_run: DateTime<Utc>,
+    ) -> Result<(), SchedulerError> {
+        self.records.set_task_next_run(id, next_run).await
+    }
+
+    async fn set_task_schedule(
+        &self,
+        id: Uuid,
+        schedule: TaskType,
+        next_run: DateTime<Utc>,
+    ) -> Result<(), SchedulerError> {
+        self.records.set_task_schedule(id, schedule, next_run).await
+    }
+
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage, SchedulerError> {
+        let mut tasks: Vec<Task> = self
+            .records
+            .get_all_tasks()
+            .await?
+            .into_iter()
+            .filter(|task| query.matches(task))
+            .collect();
+
+        tasks.sort_by_key(|task| (query.sort.key(task), task.id));
+        if query.sort.is_descending() {
+            tasks.reverse();
+        }
+
+        if let Some(cursor) = &query.cursor {
+            let cursor = (cursor.key(), cursor.id());
+            tasks.retain(|task| {
+                let position = (query.sort.key(task), task.id);
+                if query.sort.is_descending() {
+                    position < cursor
+                } else {
+                    position > cursor
+                }
+            });
+        }
+
+        let has_more = tasks.len() > query.limit;
+        tasks.truncate(query.limit);
+        let next_cursor = has_more
+            .then(|| tasks.last())
+            .flatten()
+            .map(|task| TaskCursor::new(query.sort.key(task), task.id));
+
+        Ok(TaskPage { tasks, next_cursor })
+    }
+
+    async fn get_ready_tasks(&self) -> Result<Vec<Task>, SchedulerError> {
+        self.records.get_ready_tasks().await
+    }
+
+    async fn claim_ready_tasks(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<Task>, SchedulerError> {
+        self.records
+            .claim_ready_tasks(worker_id, lease, limit)
+            .await
+    }
+
+    async fn release_task(&self, id: Uuid, worker_id: &str) -> Result<(), SchedulerError> {
+        self.records.release_task(id, worker_id).await
+    }
+
+    async fn renew_lease(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<(), SchedulerError> {
+        self.records.renew_lease(id, worker_id, lease).await
+    }
+
+    async fn next_due_time(&self) -> Result<Option<DateTime<Utc>>, SchedulerError> {
+        self.records.next_due_time().await
+    }
+
+    async fn record_task_run(&self, run: TaskRun) -> Result<(), SchedulerError> {
+        self.records.record_task_run(run).await
+    }
+
+    async fn get_task_runs(&self, task_id: Uuid) -> Result<Vec<TaskRun>, SchedulerError> {
+        self.records.get_task_runs(task_id).await
+    }
+
+    async fn save_dead_letter(&self, dead_letter: DeadLetter) -> Result<(), SchedulerError> {
+        self.records.save_dead_letter(dead_letter).await
+    }
+
+    async fn get_dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>, SchedulerError> {
+        self.records.get_dead_letter(id).await
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, SchedulerError> {
+        self.records.get_dead_letters().await
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), SchedulerError> {
+        self.records.delete_dead_letter(id).await
+    }
+}
+
+#[tokio::test]
+async fn test_external_storage_conformance() {
+    run_storage_conformance(|| async { Arc::new(ExternalStorage::default()) }).await;
+}